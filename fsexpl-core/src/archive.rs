@@ -0,0 +1,212 @@
+use std::{io::{self, Read, Seek, SeekFrom, BufReader}, path::Path, collections::HashMap};
+
+use crate::{node::{Node, Entry}, metadata::Metadata, scan::SizeMode};
+
+mod zip;
+mod sevenz;
+mod lzma;
+
+// archives whose listing can be read without extracting them
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Kind {
+	Zip,
+	Tar,
+	TarGz,
+	TarZst,
+	SevenZip,
+}
+
+impl Kind {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+		let kinds = [
+			(".zip", Kind::Zip),
+			(".tar", Kind::Tar),
+			(".tar.gz", Kind::TarGz),
+			(".tgz", Kind::TarGz),
+			(".tar.zst", Kind::TarZst),
+			(".tzst", Kind::TarZst),
+			(".7z", Kind::SevenZip),
+		];
+		kinds.into_iter().find(|(suffix, _)| name.ends_with(suffix)).map(|(_, kind)| kind)
+	}
+}
+
+// a file or directory stored in an archive
+#[derive(Clone, Debug)]
+pub struct Member {
+	pub path: String, // inside the archive, separated by slashes
+	pub is_dir: bool,
+	pub bytes: u64, // extracted
+	pub compressed: u64, // estimated from the share of the uncompressed size where members are compressed together
+	pub modified: i64,
+	pub mode: u32, // 0 if the archive doesn't say
+}
+
+// the members of an archive read from the start of the file
+pub fn list<R: Read + Seek>(mut file: R, kind: Kind) -> io::Result<Vec<Member>> {
+	let archive_bytes = file.seek(SeekFrom::End(0))?;
+	file.seek(SeekFrom::Start(0))?;
+
+	match kind {
+		Kind::Zip => zip::list(file),
+		Kind::SevenZip => sevenz::list(file),
+		Kind::Tar => {
+			let mut archive = tar::Archive::new(file);
+			let mut members = list_tar(archive.entries_with_seek()?)?;
+			// the members are skipped by seeking, which goes past the end of a truncated file without an error
+			if archive.into_inner().stream_position()? > archive_bytes {
+				return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "the tar is truncated"));
+			}
+			for member in &mut members {
+				member.compressed = member.bytes;
+			}
+			Ok(members)
+		},
+		Kind::TarGz => {
+			let mut archive = tar::Archive::new(flate2::read::MultiGzDecoder::new(BufReader::new(file)));
+			let mut members = list_tar(archive.entries()?)?;
+			// the listing stops at the end marker, the rest of the stream is read so a truncated one fails
+			io::copy(&mut archive.into_inner(), &mut io::sink())?;
+			share_out(&mut members, archive_bytes);
+			Ok(members)
+		},
+		Kind::TarZst => {
+			let mut archive = tar::Archive::new(zstd::stream::read::Decoder::new(file)?);
+			let mut members = list_tar(archive.entries()?)?;
+			// likewise, zstd checks the frame only at its end
+			io::copy(&mut archive.into_inner(), &mut io::sink())?;
+			share_out(&mut members, archive_bytes);
+			Ok(members)
+		},
+	}
+}
+
+fn list_tar<R: Read>(entries: tar::Entries<R>) -> io::Result<Vec<Member>> {
+	let mut members = Vec::new();
+
+	for entry in entries {
+		let entry = entry?;
+		let header = entry.header();
+		let path = entry.path()?.to_string_lossy().to_string();
+
+		members.push(Member {
+			path,
+			is_dir: header.entry_type().is_dir(),
+			bytes: entry.size(),
+			compressed: 0,
+			modified: header.mtime().unwrap_or(0) as i64,
+			mode: header.mode().unwrap_or(0) & 0o7777 | match header.entry_type() {
+				tar::EntryType::Directory => 0o040000,
+				tar::EntryType::Symlink => 0o120000,
+				_ => 0o100000,
+			},
+		});
+	}
+
+	Ok(members)
+}
+
+// spreads the compressed size over the members of a stream compressed as a whole
+fn share_out(members: &mut [Member], compressed: u64) {
+	let total: u64 = members.iter().map(|m| m.bytes).sum();
+	if total == 0 {
+		return;
+	}
+	for member in members {
+		member.compressed = (member.bytes as u128 * compressed as u128 / total as u128) as u64;
+	}
+}
+
+// the archive as a directory with its members below it, sized by the extracted or the compressed bytes
+// while the archive itself keeps its own size, the bytes it takes on disk
+// directories the archive only implies by the paths of its members are added
+pub fn tree(path: &Path, bytes: u64, metadata: Metadata, members: Vec<Member>, size_mode: SizeMode) -> Node {
+	let mut children: HashMap<String, Vec<String>> = HashMap::new();
+	// without the ./ of tars made in the current directory, and anything that would point outside
+	let mut members: HashMap<String, Member> = members.into_iter()
+		.filter(|member| !member.path.split('/').any(|part| part == ".."))
+		.map(|member| {
+			let parts: Vec<&str> = member.path.split('/').filter(|part| !part.is_empty() && *part != ".").collect();
+			(parts.join("/"), member)
+		})
+		.filter(|(path, _)| !path.is_empty())
+		.collect();
+
+	// every member under its parent, creating the directories on the way
+	let paths: Vec<String> = members.keys().cloned().collect();
+	for member_path in paths {
+		let mut current = member_path;
+		loop {
+			let parent = current.rsplit_once('/').map_or(String::new(), |(parent, _)| parent.to_string());
+			let siblings = children.entry(parent.clone()).or_default();
+			let known = !siblings.is_empty() || parent.is_empty() || members.contains_key(&parent);
+			siblings.push(current);
+			if known {
+				break;
+			}
+			members.insert(parent.clone(), Member { path: parent.clone(), is_dir: true, bytes: 0, compressed: 0, modified: metadata.modified, mode: 0 });
+			current = parent;
+		}
+	}
+	for siblings in children.values_mut() {
+		siblings.sort_unstable();
+		siblings.dedup();
+	}
+
+	let mut nodes: Vec<Node> = children.get("").into_iter().flatten()
+		.map(|child| build(path, child, &members, &children, &metadata, size_mode))
+		.collect();
+	nodes.sort_unstable_by_key(|n| n.entry.bytes());
+
+	Node::directory(Entry::new(path, true, bytes, metadata), nodes)
+}
+
+fn build(archive: &Path, member_path: &str, members: &HashMap<String, Member>, children: &HashMap<String, Vec<String>>, archive_metadata: &Metadata, size_mode: SizeMode) -> Node {
+	let member = &members[member_path];
+	let is_dir = member.is_dir || children.contains_key(member_path);
+
+	let metadata = Metadata {
+		modified: member.modified,
+		mode: if member.mode != 0 { member.mode } else if is_dir { 0o040755 } else { 0o100644 },
+		uid: archive_metadata.uid,
+		gid: archive_metadata.gid,
+		inode: 0,
+		device: archive_metadata.device,
+		blocks: member.compressed.div_ceil(512),
+		storage_class: None,
+	};
+	let path = archive.join(member_path);
+
+	if !is_dir {
+		let bytes = match size_mode {
+			SizeMode::Apparent => member.bytes,
+			SizeMode::Disk => member.compressed,
+		};
+		return Node::file(Entry::new(&path, false, bytes, metadata));
+	}
+
+	let mut nodes: Vec<Node> = children.get(member_path).into_iter().flatten()
+		.map(|child| build(archive, child, members, children, archive_metadata, size_mode))
+		.collect();
+	nodes.sort_unstable_by_key(|n| n.entry.bytes());
+
+	Node::directory(Entry::new(&path, true, 0, metadata), nodes)
+}
+
+// little endian fields of the binary formats
+fn u16_at(bytes: &[u8], at: usize) -> io::Result<u16> {
+	bytes.get(at..at + 2).map(|b| u16::from_le_bytes([b[0], b[1]])).ok_or_else(|| invalid("truncated archive"))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> io::Result<u32> {
+	bytes.get(at..at + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])).ok_or_else(|| invalid("truncated archive"))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> io::Result<u64> {
+	Ok(u32_at(bytes, at)? as u64 | (u32_at(bytes, at + 4)? as u64) << 32)
+}
+
+fn invalid(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
@@ -0,0 +1,483 @@
+use std::io;
+
+use super::invalid;
+
+// lzma and lzma2 decoders for the compressed headers of 7z archives, which are small enough to decode in memory
+// it follows the reference decoder in the lzma sdk (LzmaSpec.cpp): a range coder driving adaptive bit
+// probabilities, literals coded against the byte at the last match distance, and matches coded as a
+// length plus a distance or one of the four last distances
+
+const NUM_STATES: usize = 12;
+const NUM_POS_BITS_MAX: usize = 4;
+const NUM_LEN_TO_POS_STATES: usize = 4;
+const END_POS_MODEL_INDEX: u32 = 14;
+const NUM_FULL_DISTANCES: usize = 1 << (END_POS_MODEL_INDEX >> 1);
+const NUM_ALIGN_BITS: u32 = 4;
+const MATCH_MIN_LEN: usize = 2;
+const PROBABILITY_BITS: u32 = 11;
+const PROBABILITY_INIT: u16 = 1 << (PROBABILITY_BITS - 1);
+const MOVE_BITS: u32 = 5;
+
+// properties are the 5 bytes 7z stores with the coder: lc, lp and pb packed into one, then the dictionary size
+pub fn decode(properties: &[u8], input: &[u8], unpacked_size: usize) -> io::Result<Vec<u8>> {
+	let &[packed, ..] = properties else {
+		return Err(invalid("missing lzma properties"));
+	};
+
+	let mut decoder = Decoder::new(packed, unpacked_size)?;
+	let mut range = RangeDecoder::new(input)?;
+	decoder.run(&mut range, unpacked_size)?;
+	if decoder.out.len() < unpacked_size {
+		return Err(invalid("truncated lzma stream"));
+	}
+
+	Ok(decoder.out)
+}
+
+// lzma2 splits the stream into chunks, each either stored or lzma with a fresh range coder,
+// and says per chunk whether the state, the properties or the whole dictionary start over
+pub fn decode2(input: &[u8], unpacked_size: usize) -> io::Result<Vec<u8>> {
+	let mut decoder: Option<Decoder> = None;
+	let mut out = Vec::with_capacity(unpacked_size);
+	let mut at = 0;
+
+	let header = |at: usize, length: usize| input.get(at..at + length).ok_or_else(|| invalid("truncated lzma2 stream"));
+	// every chunk can claim up to 2M, a corrupt stream could otherwise grow far beyond the size 7z gave
+	let check_size = |decoded: usize| if decoded > unpacked_size { Err(invalid("lzma2 stream longer than its size")) } else { Ok(()) };
+
+	loop {
+		let control = header(at, 1)?[0];
+		at += 1;
+
+		match control {
+			0x00 => break,
+			0x01 | 0x02 => {
+				let fields = header(at, 2)?;
+				let size = ((fields[0] as usize) << 8 | fields[1] as usize) + 1;
+				at += 2;
+				let chunk = header(at, size)?;
+				at += size;
+				check_size(decoder.as_ref().map_or(out.len(), |d| d.out.len()) + size)?;
+				match &mut decoder {
+					Some(decoder) => decoder.out.extend_from_slice(chunk),
+					None => out.extend_from_slice(chunk),
+				}
+			},
+			0x80.. => {
+				let fields = header(at, 4)?;
+				let unpacked = ((control as usize & 0x1f) << 16 | (fields[0] as usize) << 8 | fields[1] as usize) + 1;
+				let packed = ((fields[2] as usize) << 8 | fields[3] as usize) + 1;
+				at += 4;
+
+				let reset = (control >> 5) & 3;
+				if reset >= 2 {
+					let properties = header(at, 1)?[0];
+					at += 1;
+					let previous = decoder.take().map_or(std::mem::take(&mut out), |d| d.out);
+					let mut next = Decoder::new(properties, unpacked_size)?;
+					next.out = previous;
+					decoder = Some(next);
+				}
+				let decoder = decoder.as_mut().ok_or_else(|| invalid("lzma2 chunk without properties"))?;
+				if reset >= 1 {
+					decoder.reset();
+				}
+
+				let mut range = RangeDecoder::new(header(at, packed)?)?;
+				at += packed;
+				let end = decoder.out.len() + unpacked;
+				check_size(end)?;
+				decoder.run(&mut range, end)?;
+				if decoder.out.len() < end {
+					return Err(invalid("truncated lzma2 chunk"));
+				}
+			},
+			_ => return Err(invalid("bad lzma2 chunk")),
+		}
+	}
+
+	Ok(decoder.map_or(out, |d| d.out))
+}
+
+struct RangeDecoder<'a> {
+	input: &'a [u8],
+	at: usize,
+	range: u32,
+	code: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+	fn new(input: &'a [u8]) -> io::Result<Self> {
+		let mut decoder = Self { input, at: 0, range: 0xffffffff, code: 0 };
+		if decoder.byte()? != 0 {
+			return Err(invalid("bad lzma stream"));
+		}
+		for _ in 0..4 {
+			decoder.code = decoder.code << 8 | decoder.byte()? as u32;
+		}
+		if decoder.code == decoder.range {
+			return Err(invalid("bad lzma stream"));
+		}
+		Ok(decoder)
+	}
+
+	fn byte(&mut self) -> io::Result<u8> {
+		let byte = *self.input.get(self.at).ok_or_else(|| invalid("truncated lzma stream"))?;
+		self.at += 1;
+		Ok(byte)
+	}
+
+	fn normalize(&mut self) -> io::Result<()> {
+		if self.range < 1 << 24 {
+			self.range <<= 8;
+			self.code = self.code << 8 | self.byte()? as u32;
+		}
+		Ok(())
+	}
+
+	fn direct_bits(&mut self, count: u32) -> io::Result<u32> {
+		let mut result = 0;
+		for _ in 0..count {
+			self.range >>= 1;
+			let bit = (self.code >= self.range) as u32;
+			if bit == 1 {
+				self.code -= self.range;
+			}
+			result = result << 1 | bit;
+			self.normalize()?;
+		}
+		Ok(result)
+	}
+
+	fn bit(&mut self, probability: &mut u16) -> io::Result<u32> {
+		let bound = (self.range >> PROBABILITY_BITS) * *probability as u32;
+		let bit = if self.code < bound {
+			*probability += ((1 << PROBABILITY_BITS) - *probability) >> MOVE_BITS;
+			self.range = bound;
+			0
+		}
+		else {
+			*probability -= *probability >> MOVE_BITS;
+			self.code -= bound;
+			self.range -= bound;
+			1
+		};
+		self.normalize()?;
+		Ok(bit)
+	}
+
+	// bits from the most significant one, each with the probability at the node of the tree walked so far
+	fn tree(&mut self, probabilities: &mut [u16], bits: u32) -> io::Result<u32> {
+		let mut m = 1;
+		for _ in 0..bits {
+			m = m << 1 | self.bit(&mut probabilities[m as usize])?;
+		}
+		Ok(m - (1 << bits))
+	}
+
+	fn reverse_tree(&mut self, probabilities: &mut [u16], bits: u32) -> io::Result<u32> {
+		let mut m = 1;
+		let mut symbol = 0;
+		for i in 0..bits {
+			let bit = self.bit(&mut probabilities[m as usize])?;
+			m = m << 1 | bit;
+			symbol |= bit << i;
+		}
+		Ok(symbol)
+	}
+}
+
+struct LengthDecoder {
+	choice: u16,
+	choice2: u16,
+	low: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+	mid: [[u16; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+	high: [u16; 1 << 8],
+}
+
+impl LengthDecoder {
+	fn new() -> Self {
+		Self {
+			choice: PROBABILITY_INIT,
+			choice2: PROBABILITY_INIT,
+			low: [[PROBABILITY_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+			mid: [[PROBABILITY_INIT; 1 << 3]; 1 << NUM_POS_BITS_MAX],
+			high: [PROBABILITY_INIT; 1 << 8],
+		}
+	}
+
+	// the match length minus the minimum of 2
+	fn decode(&mut self, range: &mut RangeDecoder, pos_state: usize) -> io::Result<usize> {
+		if range.bit(&mut self.choice)? == 0 {
+			return Ok(range.tree(&mut self.low[pos_state], 3)? as usize);
+		}
+		if range.bit(&mut self.choice2)? == 0 {
+			return Ok(8 + range.tree(&mut self.mid[pos_state], 3)? as usize);
+		}
+		Ok(16 + range.tree(&mut self.high, 8)? as usize)
+	}
+}
+
+struct Decoder {
+	out: Vec<u8>,
+	lc: u32,
+	lp: u32,
+	pb: u32,
+	state: usize,
+	reps: [usize; 4],
+	literals: Vec<u16>,
+	is_match: [u16; NUM_STATES << NUM_POS_BITS_MAX],
+	is_rep: [u16; NUM_STATES],
+	is_rep_g0: [u16; NUM_STATES],
+	is_rep_g1: [u16; NUM_STATES],
+	is_rep_g2: [u16; NUM_STATES],
+	is_rep0_long: [u16; NUM_STATES << NUM_POS_BITS_MAX],
+	pos_slot: [[u16; 1 << 6]; NUM_LEN_TO_POS_STATES],
+	pos: [u16; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize],
+	align: [u16; 1 << NUM_ALIGN_BITS],
+	length: LengthDecoder,
+	rep_length: LengthDecoder,
+}
+
+impl Decoder {
+	fn new(properties: u8, capacity: usize) -> io::Result<Self> {
+		if properties >= 9 * 5 * 5 {
+			return Err(invalid("bad lzma properties"));
+		}
+		let lc = (properties % 9) as u32;
+		let lp = (properties / 9 % 5) as u32;
+		let pb = (properties / 45) as u32;
+
+		Ok(Self {
+			out: Vec::with_capacity(capacity),
+			lc, lp, pb,
+			state: 0,
+			reps: [0; 4],
+			literals: vec![PROBABILITY_INIT; 0x300 << (lc + lp)],
+			is_match: [PROBABILITY_INIT; NUM_STATES << NUM_POS_BITS_MAX],
+			is_rep: [PROBABILITY_INIT; NUM_STATES],
+			is_rep_g0: [PROBABILITY_INIT; NUM_STATES],
+			is_rep_g1: [PROBABILITY_INIT; NUM_STATES],
+			is_rep_g2: [PROBABILITY_INIT; NUM_STATES],
+			is_rep0_long: [PROBABILITY_INIT; NUM_STATES << NUM_POS_BITS_MAX],
+			pos_slot: [[PROBABILITY_INIT; 1 << 6]; NUM_LEN_TO_POS_STATES],
+			pos: [PROBABILITY_INIT; 1 + NUM_FULL_DISTANCES - END_POS_MODEL_INDEX as usize],
+			align: [PROBABILITY_INIT; 1 << NUM_ALIGN_BITS],
+			length: LengthDecoder::new(),
+			rep_length: LengthDecoder::new(),
+		})
+	}
+
+	// the probabilities and state start over, the output stays as the dictionary
+	fn reset(&mut self) {
+		let out = std::mem::take(&mut self.out);
+		let properties = (self.pb * 5 + self.lp) * 9 + self.lc;
+		*self = Self::new(properties as u8, 0).expect("valid properties");
+		self.out = out;
+	}
+
+	// decodes until the output reaches the end or the end marker
+	fn run(&mut self, range: &mut RangeDecoder, end: usize) -> io::Result<()> {
+		let mut state = self.state;
+		let mut reps = self.reps;
+
+		while self.out.len() < end {
+			let pos_state = self.out.len() & ((1 << self.pb) - 1);
+
+			if range.bit(&mut self.is_match[(state << NUM_POS_BITS_MAX) + pos_state])? == 0 {
+				self.literal(range, state, reps[0])?;
+				state = if state < 4 { 0 } else if state < 10 { state - 3 } else { state - 6 };
+				continue;
+			}
+
+			let length;
+			if range.bit(&mut self.is_rep[state])? != 0 {
+				if self.out.is_empty() {
+					return Err(invalid("bad lzma stream"));
+				}
+				if range.bit(&mut self.is_rep_g0[state])? == 0 {
+					// a single byte at the last distance
+					if range.bit(&mut self.is_rep0_long[(state << NUM_POS_BITS_MAX) + pos_state])? == 0 {
+						state = if state < 7 { 9 } else { 11 };
+						let byte = self.out[self.out.len() - reps[0] - 1];
+						self.out.push(byte);
+						continue;
+					}
+				}
+				else {
+					let distance;
+					if range.bit(&mut self.is_rep_g1[state])? == 0 {
+						distance = reps[1];
+					}
+					else {
+						if range.bit(&mut self.is_rep_g2[state])? == 0 {
+							distance = reps[2];
+						}
+						else {
+							distance = reps[3];
+							reps[3] = reps[2];
+						}
+						reps[2] = reps[1];
+					}
+					reps[1] = reps[0];
+					reps[0] = distance;
+				}
+				length = self.rep_length.decode(range, pos_state)?;
+				state = if state < 7 { 8 } else { 11 };
+			}
+			else {
+				reps[3] = reps[2];
+				reps[2] = reps[1];
+				reps[1] = reps[0];
+				length = self.length.decode(range, pos_state)?;
+				state = if state < 7 { 7 } else { 10 };
+				match self.distance(range, length)? {
+					0xffffffff => break, // the end marker
+					distance => reps[0] = distance as usize,
+				}
+			}
+
+			if reps[0] >= self.out.len() {
+				return Err(invalid("bad lzma distance"));
+			}
+			let length = (length + MATCH_MIN_LEN).min(end - self.out.len());
+			for _ in 0..length {
+				let byte = self.out[self.out.len() - reps[0] - 1];
+				self.out.push(byte);
+			}
+		}
+
+		self.state = state;
+		self.reps = reps;
+		Ok(())
+	}
+
+	fn literal(&mut self, range: &mut RangeDecoder, state: usize, rep0: usize) -> io::Result<()> {
+		let previous = self.out.last().copied().unwrap_or(0) as usize;
+		let literal_state = ((self.out.len() & ((1 << self.lp) - 1)) << self.lc) + (previous >> (8 - self.lc));
+		let probabilities = &mut self.literals[0x300 * literal_state..0x300 * (literal_state + 1)];
+
+		let mut symbol = 1usize;
+		// after a match the byte at the match distance predicts the bits until the first that differs
+		if state >= 7 && rep0 < self.out.len() {
+			let mut match_byte = self.out[self.out.len() - rep0 - 1] as usize;
+			while symbol < 0x100 {
+				let match_bit = (match_byte >> 7) & 1;
+				match_byte <<= 1;
+				let bit = range.bit(&mut probabilities[((1 + match_bit) << 8) + symbol])? as usize;
+				symbol = symbol << 1 | bit;
+				if match_bit != bit {
+					break;
+				}
+			}
+		}
+		while symbol < 0x100 {
+			symbol = symbol << 1 | range.bit(&mut probabilities[symbol])? as usize;
+		}
+
+		self.out.push((symbol - 0x100) as u8);
+		Ok(())
+	}
+
+	fn distance(&mut self, range: &mut RangeDecoder, length: usize) -> io::Result<u32> {
+		let length_state = length.min(NUM_LEN_TO_POS_STATES - 1);
+		let slot = range.tree(&mut self.pos_slot[length_state], 6)?;
+		if slot < 4 {
+			return Ok(slot);
+		}
+
+		let direct_bits = (slot >> 1) - 1;
+		let mut distance = (2 | (slot & 1)) << direct_bits;
+		if slot < END_POS_MODEL_INDEX {
+			let base = (distance - slot) as usize;
+			distance += range.reverse_tree(&mut self.pos[base..], direct_bits)?;
+		}
+		else {
+			distance += range.direct_bits(direct_bits - NUM_ALIGN_BITS)? << NUM_ALIGN_BITS;
+			distance += range.reverse_tree(&mut self.align, NUM_ALIGN_BITS)?;
+		}
+		Ok(distance)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// fixtures in tests/data made with python's lzma module as raw streams; raw.lzma2 is assembled chunk by chunk:
+	// stored with a dictionary reset, lzma with new properties continued over a second chunk, stored, lzma with a state reset
+
+	const FOX: &str = "the quick brown fox\n";
+
+	// xorshift bytes lzma can't shrink, ending in zero so a chunk encoded on its own can follow them
+	fn noise(length: usize, mut x: u32) -> Vec<u8> {
+		let mut bytes: Vec<u8> = (0..length).map(|_| {
+			x ^= x << 13;
+			x ^= x >> 17;
+			x ^= x << 5;
+			x as u8
+		}).collect();
+		bytes[length - 1] = 0;
+		bytes
+	}
+
+	fn lzma2_contents() -> Vec<u8> {
+		let mut contents = noise(500, 0x12345678);
+		contents.extend(FOX.repeat(110000).bytes());
+		contents.extend(noise(300, 0x9e3779b9));
+		contents.extend("jumps over the lazy dog\n".repeat(50).bytes());
+		contents
+	}
+
+	// lc 1, lp 2 and pb 1 packed as (pb * 5 + lp) * 9 + lc, then a 64K dictionary
+	const PROPERTIES: [u8; 5] = [64, 0, 0, 1, 0];
+
+	#[test]
+	fn decodes_lzma() {
+		let mut contents = noise(1000, 0x12345678);
+		contents.extend(FOX.repeat(3000).bytes());
+
+		let input = include_bytes!("../../tests/data/raw.lzma");
+		assert_eq!(decode(&PROPERTIES, input, contents.len()).unwrap(), contents);
+	}
+
+	#[test]
+	fn decodes_lzma2_chunks() {
+		let contents = lzma2_contents();
+
+		let input = include_bytes!("../../tests/data/raw.lzma2");
+		assert_eq!(decode2(input, contents.len()).unwrap(), contents);
+	}
+
+	#[test]
+	fn rejects_truncated_streams() {
+		let input = include_bytes!("../../tests/data/raw.lzma");
+		for length in [0, 1, 4, 100, input.len() / 2] {
+			assert!(decode(&PROPERTIES, &input[..length], 61000).is_err());
+		}
+		assert!(decode(&[], input, 61000).is_err());
+
+		let input = include_bytes!("../../tests/data/raw.lzma2");
+		let contents = lzma2_contents().len();
+		for length in [0, 1, 2, 300, 510, 600, input.len() - 1] {
+			assert!(decode2(&input[..length], contents).is_err());
+		}
+	}
+
+	#[test]
+	fn survives_corrupt_streams() {
+		// errors or garbage are both fine, as long as nothing panics or grows past the expected size
+		let length = lzma2_contents().len();
+		for input in [&include_bytes!("../../tests/data/raw.lzma")[..], &include_bytes!("../../tests/data/raw.lzma2")[..]] {
+			for at in (0..input.len()).step_by(37) {
+				let mut corrupt = input.to_vec();
+				corrupt[at] ^= 0x55;
+				let _ = decode(&PROPERTIES, &corrupt, 61000);
+				let _ = decode2(&corrupt, length);
+				if let Ok(out) = decode2(&corrupt, 4096) {
+					assert!(out.len() <= 4096);
+				}
+			}
+		}
+	}
+}
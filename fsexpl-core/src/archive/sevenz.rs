@@ -0,0 +1,489 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{Member, lzma, u32_at, u64_at, invalid};
+
+// the header at the end of a 7z file describes the packed streams, the folders (chains of coders) that
+// unpack them, how the unpacked output of each folder splits into files, and the file properties
+// it is usually compressed itself, as an encoded header that only describes how to unpack the real one
+// https://github.com/ip7z/7zip/blob/main/DOC/7zFormat.txt
+
+const SIGNATURE: &[u8] = b"7z\xbc\xaf\x27\x1c";
+const SIGNATURE_HEADER_SIZE: u64 = 32;
+const MAX_HEADER_SIZE: u64 = 1 << 30;
+const LZMA: &[u8] = &[3, 1, 1];
+const LZMA2: &[u8] = &[0x21];
+
+const END: u8 = 0x00;
+const HEADER: u8 = 0x01;
+const ARCHIVE_PROPERTIES: u8 = 0x02;
+const ADDITIONAL_STREAMS_INFO: u8 = 0x03;
+const MAIN_STREAMS_INFO: u8 = 0x04;
+const FILES_INFO: u8 = 0x05;
+const PACK_INFO: u8 = 0x06;
+const UNPACK_INFO: u8 = 0x07;
+const SUBSTREAMS_INFO: u8 = 0x08;
+const SIZE: u8 = 0x09;
+const CRC: u8 = 0x0a;
+const FOLDER: u8 = 0x0b;
+const CODERS_UNPACK_SIZE: u8 = 0x0c;
+const NUM_UNPACK_STREAM: u8 = 0x0d;
+const EMPTY_STREAM: u8 = 0x0e;
+const EMPTY_FILE: u8 = 0x0f;
+const NAME: u8 = 0x11;
+const MODIFIED: u8 = 0x14;
+const ATTRIBUTES: u8 = 0x15;
+const ENCODED_HEADER: u8 = 0x17;
+
+pub fn list<R: Read + Seek>(mut file: R) -> io::Result<Vec<Member>> {
+	let mut start = [0; SIGNATURE_HEADER_SIZE as usize];
+	file.read_exact(&mut start)?;
+	if !start.starts_with(SIGNATURE) {
+		return Err(invalid("not a 7z archive"));
+	}
+
+	let header_offset = u64_at(&start, 12)?;
+	let header_size = u64_at(&start, 20)?;
+	if header_size == 0 {
+		return Ok(Vec::new());
+	}
+	if header_size > MAX_HEADER_SIZE {
+		return Err(invalid("7z header too big"));
+	}
+
+	let mut header = vec![0; header_size as usize];
+	file.seek(SeekFrom::Start(SIGNATURE_HEADER_SIZE.checked_add(header_offset).ok_or_else(|| invalid("bad 7z header offset"))?))?;
+	file.read_exact(&mut header)?;
+
+	loop {
+		let mut reader = Reader { bytes: &header, at: 0 };
+		match reader.byte()? {
+			HEADER => return read_header(&mut reader),
+			ENCODED_HEADER => {
+				let streams = read_streams_info(&mut reader)?;
+				header = unpack_header(&mut file, &streams)?;
+			},
+			_ => return Err(invalid("bad 7z header")),
+		}
+	}
+}
+
+#[derive(Default)]
+struct Folder {
+	coders: Vec<(Vec<u8>, Vec<u8>)>, // method id and properties
+	packed_streams: usize,
+	unpack_sizes: Vec<u64>, // one per coder output
+	bound_outputs: Vec<u64>, // outputs feeding another coder
+	has_digest: bool,
+}
+
+impl Folder {
+	// the size of the output no other coder consumes
+	fn unpack_size(&self) -> u64 {
+		(0..self.unpack_sizes.len())
+			.find(|&i| !self.bound_outputs.contains(&(i as u64)))
+			.map_or(0, |i| self.unpack_sizes[i])
+	}
+}
+
+#[derive(Default)]
+struct StreamsInfo {
+	pack_position: u64,
+	pack_sizes: Vec<u64>,
+	folders: Vec<Folder>,
+	substreams: Vec<Vec<u64>>, // the unpacked sizes of the files in each folder
+}
+
+// the encoded header is a single folder compressed with lzma, or lzma2 by some writers
+fn unpack_header<R: Read + Seek>(file: &mut R, streams: &StreamsInfo) -> io::Result<Vec<u8>> {
+	let (Some(folder), Some(&packed_size)) = (streams.folders.first(), streams.pack_sizes.first()) else {
+		return Err(invalid("empty 7z encoded header"));
+	};
+	let [(method, properties)] = folder.coders.as_slice() else {
+		return Err(invalid("unsupported 7z header compression"));
+	};
+	if packed_size > MAX_HEADER_SIZE || folder.unpack_size() > MAX_HEADER_SIZE {
+		return Err(invalid("7z header too big"));
+	}
+
+	let mut packed = vec![0; packed_size as usize];
+	file.seek(SeekFrom::Start(SIGNATURE_HEADER_SIZE.checked_add(streams.pack_position).ok_or_else(|| invalid("bad 7z pack position"))?))?;
+	file.read_exact(&mut packed)?;
+
+	match method.as_slice() {
+		LZMA => lzma::decode(properties, &packed, folder.unpack_size() as usize),
+		LZMA2 => lzma::decode2(&packed, folder.unpack_size() as usize),
+		_ => Err(invalid("unsupported 7z header compression")),
+	}
+}
+
+fn read_header(reader: &mut Reader) -> io::Result<Vec<Member>> {
+	let mut streams = StreamsInfo::default();
+	let mut id = reader.byte()?;
+
+	if id == ARCHIVE_PROPERTIES {
+		while reader.byte()? != END {
+			let size = reader.number()?;
+			reader.skip(size)?;
+		}
+		id = reader.byte()?;
+	}
+	if id == ADDITIONAL_STREAMS_INFO {
+		read_streams_info(reader)?;
+		id = reader.byte()?;
+	}
+	if id == MAIN_STREAMS_INFO {
+		streams = read_streams_info(reader)?;
+		id = reader.byte()?;
+	}
+	if id != FILES_INFO {
+		return Ok(Vec::new());
+	}
+
+	let files = read_files_info(reader)?;
+
+	// the files with data take the substreams in order, folder after folder
+	let mut sizes = streams.folders.iter().zip(&streams.substreams).enumerate().flat_map(|(i, (folder, substreams))| {
+		let packed = pack_sizes_of(&streams, i).iter().fold(0u64, |sum, &size| sum.saturating_add(size));
+		let unpacked = folder.unpack_size().max(1);
+		substreams.iter().map(move |&size| (size, (size as u128 * packed as u128 / unpacked as u128) as u64))
+	});
+
+	let mut members = Vec::new();
+	for file in files {
+		let (bytes, compressed) = if file.has_stream { sizes.next().unwrap_or_default() } else { (0, 0) };
+		members.push(Member {
+			path: file.name,
+			is_dir: file.is_dir,
+			bytes,
+			compressed,
+			modified: file.modified,
+			mode: file.mode,
+		});
+	}
+
+	Ok(members)
+}
+
+// the packed streams of a folder, which are consumed in folder order
+fn pack_sizes_of(streams: &StreamsInfo, folder: usize) -> &[u64] {
+	let first: usize = streams.folders[..folder].iter().map(|f| f.packed_streams).sum();
+	let end = (first + streams.folders[folder].packed_streams).min(streams.pack_sizes.len());
+	streams.pack_sizes.get(first..end).unwrap_or_default()
+}
+
+fn read_streams_info(reader: &mut Reader) -> io::Result<StreamsInfo> {
+	let mut streams = StreamsInfo::default();
+
+	loop {
+		match reader.byte()? {
+			END => break,
+			PACK_INFO => {
+				streams.pack_position = reader.number()?;
+				let count = reader.count()?;
+				loop {
+					match reader.byte()? {
+						END => break,
+						SIZE => streams.pack_sizes = (0..count).map(|_| reader.number()).collect::<io::Result<_>>()?,
+						CRC => reader.digests(count).map(|_| ())?,
+						_ => return Err(invalid("bad 7z pack info")),
+					}
+				}
+			},
+			UNPACK_INFO => {
+				if reader.byte()? != FOLDER {
+					return Err(invalid("bad 7z unpack info"));
+				}
+				let count = reader.count()?;
+				if reader.byte()? != 0 {
+					return Err(invalid("unsupported external 7z folders"));
+				}
+				streams.folders = (0..count).map(|_| read_folder(reader)).collect::<io::Result<_>>()?;
+
+				if reader.byte()? != CODERS_UNPACK_SIZE {
+					return Err(invalid("bad 7z unpack info"));
+				}
+				for folder in &mut streams.folders {
+					for size in &mut folder.unpack_sizes {
+						*size = reader.number()?;
+					}
+				}
+				loop {
+					match reader.byte()? {
+						END => break,
+						CRC => {
+							for (folder, defined) in streams.folders.iter_mut().zip(reader.digests(count)?) {
+								folder.has_digest = defined;
+							}
+						},
+						_ => return Err(invalid("bad 7z unpack info")),
+					}
+				}
+			},
+			SUBSTREAMS_INFO => streams.substreams = read_substreams(reader, &streams.folders)?,
+			_ => return Err(invalid("bad 7z streams info")),
+		}
+	}
+
+	// without substreams every folder is a single file
+	if streams.substreams.is_empty() {
+		streams.substreams = streams.folders.iter().map(|f| vec![f.unpack_size()]).collect();
+	}
+
+	Ok(streams)
+}
+
+fn read_folder(reader: &mut Reader) -> io::Result<Folder> {
+	let mut folder = Folder::default();
+	let mut inputs: usize = 0;
+	let mut outputs: usize = 0;
+
+	for _ in 0..reader.count()? {
+		let flags = reader.byte()?;
+		let method = reader.bytes((flags & 0x0f) as u64)?.to_vec();
+		let (coder_inputs, coder_outputs) = if flags & 0x10 != 0 { (reader.count()?, reader.count()?) } else { (1, 1) };
+		let properties = if flags & 0x20 != 0 {
+			let size = reader.number()?;
+			reader.bytes(size)?.to_vec()
+		}
+		else {
+			Vec::new()
+		};
+		// every output has its size stored later, so there can't be more than the header has bytes
+		inputs = reader.bound(inputs as u64 + coder_inputs as u64)?;
+		outputs = reader.bound(outputs as u64 + coder_outputs as u64)?;
+		folder.coders.push((method, properties));
+	}
+
+	for _ in 0..outputs.saturating_sub(1) {
+		reader.number()?; // the input it feeds
+		folder.bound_outputs.push(reader.number()?);
+	}
+
+	let packed_streams = inputs.saturating_sub(outputs.saturating_sub(1));
+	if packed_streams > 1 {
+		for _ in 0..packed_streams {
+			reader.number()?;
+		}
+	}
+
+	folder.packed_streams = packed_streams;
+	folder.unpack_sizes = vec![0; outputs];
+	Ok(folder)
+}
+
+fn read_substreams(reader: &mut Reader, folders: &[Folder]) -> io::Result<Vec<Vec<u64>>> {
+	let mut counts = vec![1; folders.len()];
+	let mut substreams: Vec<Vec<u64>> = Vec::new();
+	let mut id = reader.byte()?;
+
+	if id == NUM_UNPACK_STREAM {
+		for count in &mut counts {
+			*count = reader.count()?;
+		}
+		id = reader.byte()?;
+	}
+
+	// sizes are stored for all but the last file of a folder, which gets the rest
+	let has_sizes = id == SIZE;
+	for (folder, &count) in folders.iter().zip(&counts) {
+		let mut sizes = Vec::new();
+		if count == 0 {
+			substreams.push(sizes);
+			continue;
+		}
+		let mut sum = 0;
+		if has_sizes {
+			for _ in 1..count {
+				let size = reader.number()?;
+				sum = size.checked_add(sum).ok_or_else(|| invalid("bad 7z substream sizes"))?;
+				sizes.push(size);
+			}
+		}
+		sizes.push(folder.unpack_size().saturating_sub(sum));
+		substreams.push(sizes);
+	}
+	if has_sizes {
+		id = reader.byte()?;
+	}
+
+	while id != END {
+		if id == CRC {
+			// digests for the streams whose folder digest doesn't cover them
+			let unknown: u64 = folders.iter().zip(&counts).map(|(folder, &count)| if count == 1 && folder.has_digest { 0 } else { count as u64 }).sum();
+			reader.digests(reader.bound(unknown)?)?;
+		}
+		else {
+			let size = reader.number()?;
+			reader.skip(size)?;
+		}
+		id = reader.byte()?;
+	}
+
+	Ok(substreams)
+}
+
+struct FileInfo {
+	name: String,
+	has_stream: bool,
+	is_dir: bool,
+	modified: i64,
+	mode: u32,
+}
+
+fn read_files_info(reader: &mut Reader) -> io::Result<Vec<FileInfo>> {
+	let count = reader.count()?;
+	let mut files: Vec<FileInfo> = (0..count)
+		.map(|_| FileInfo { name: String::new(), has_stream: true, is_dir: false, modified: 0, mode: 0 })
+		.collect();
+	let mut empty_streams = Vec::new();
+	let mut empty_files = Vec::new();
+
+	loop {
+		let id = reader.byte()?;
+		if id == END {
+			break;
+		}
+		let size = reader.number()?;
+		let mut property = Reader { bytes: reader.bytes(size)?, at: 0 };
+
+		match id {
+			EMPTY_STREAM => empty_streams = property.bits(count)?,
+			EMPTY_FILE => empty_files = property.bits(empty_streams.iter().filter(|&&b| b).count())?,
+			NAME => {
+				if property.byte()? != 0 {
+					return Err(invalid("unsupported external 7z names"));
+				}
+				let units: Vec<u16> = property.bytes.get(1..).unwrap_or_default()
+					.chunks_exact(2)
+					.map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+					.collect();
+				for (file, name) in files.iter_mut().zip(units.split(|&unit| unit == 0)) {
+					file.name = String::from_utf16_lossy(name);
+				}
+			},
+			MODIFIED => {
+				let defined = property.defined(count)?;
+				if property.byte()? != 0 {
+					return Err(invalid("unsupported external 7z times"));
+				}
+				for (file, _) in files.iter_mut().zip(&defined).filter(|(_, &d)| d) {
+					// windows file time, 100ns ticks since 1601
+					let ticks = property.u64()?;
+					file.modified = (ticks / 10_000_000) as i64 - 11_644_473_600;
+				}
+			},
+			ATTRIBUTES => {
+				let defined = property.defined(count)?;
+				if property.byte()? != 0 {
+					return Err(invalid("unsupported external 7z attributes"));
+				}
+				for (file, _) in files.iter_mut().zip(&defined).filter(|(_, &d)| d) {
+					let attributes = property.u32()?;
+					file.is_dir |= attributes & 0x10 != 0;
+					// the unix mode in the upper half, written by p7zip and libarchive
+					if attributes & 0x8000 != 0 {
+						file.mode = attributes >> 16;
+					}
+				}
+			},
+			_ => {},
+		}
+	}
+
+	// files without data are directories, unless marked as empty files
+	let mut empty_index = 0;
+	for (file, &empty) in files.iter_mut().zip(&empty_streams) {
+		if empty {
+			file.has_stream = false;
+			file.is_dir |= !empty_files.get(empty_index).copied().unwrap_or(false);
+			empty_index += 1;
+		}
+	}
+
+	Ok(files)
+}
+
+struct Reader<'a> {
+	bytes: &'a [u8],
+	at: usize,
+}
+
+impl<'a> Reader<'a> {
+	fn byte(&mut self) -> io::Result<u8> {
+		Ok(self.bytes(1)?[0])
+	}
+
+	fn bytes(&mut self, count: u64) -> io::Result<&'a [u8]> {
+		let end = usize::try_from(count).ok().and_then(|count| self.at.checked_add(count)).filter(|&end| end <= self.bytes.len());
+		let end = end.ok_or_else(|| invalid("truncated 7z header"))?;
+		let bytes = &self.bytes[self.at..end];
+		self.at = end;
+		Ok(bytes)
+	}
+
+	fn skip(&mut self, count: u64) -> io::Result<()> {
+		self.bytes(count).map(|_| ())
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		u32_at(self.bytes(4)?, 0)
+	}
+
+	fn u64(&mut self) -> io::Result<u64> {
+		u64_at(self.bytes(8)?, 0)
+	}
+
+	// the leading one bits of the first byte tell how many more bytes follow, little endian
+	fn number(&mut self) -> io::Result<u64> {
+		let first = self.byte()?;
+		let mut mask = 0x80;
+		let mut value = 0;
+
+		for i in 0..8 {
+			if first & mask == 0 {
+				let high = (first & (mask - 1)) as u64;
+				return Ok(value | high << (8 * i));
+			}
+			value |= (self.byte()? as u64) << (8 * i);
+			mask >>= 1;
+		}
+		Ok(value)
+	}
+
+	// a number of things to allocate for, bounded by what the header could possibly describe
+	fn count(&mut self) -> io::Result<usize> {
+		let count = self.number()?;
+		self.bound(count)
+	}
+
+	// the same bound for counts added up from others
+	fn bound(&self, count: u64) -> io::Result<usize> {
+		if count > self.bytes.len() as u64 {
+			return Err(invalid("bad 7z count"));
+		}
+		Ok(count as usize)
+	}
+
+	// most significant bit first
+	fn bits(&mut self, count: usize) -> io::Result<Vec<bool>> {
+		let bytes = self.bytes(count.div_ceil(8) as u64)?;
+		Ok((0..count).map(|i| bytes[i / 8] & (0x80 >> (i % 8)) != 0).collect())
+	}
+
+	// an all defined flag, or a bit per item
+	fn defined(&mut self, count: usize) -> io::Result<Vec<bool>> {
+		if self.byte()? != 0 {
+			Ok(vec![true; count])
+		}
+		else {
+			self.bits(count)
+		}
+	}
+
+	// skips the crcs, telling which items had one
+	fn digests(&mut self, count: usize) -> io::Result<Vec<bool>> {
+		let defined = self.defined(count)?;
+		self.skip(4 * defined.iter().filter(|&&d| d).count() as u64)?;
+		Ok(defined)
+	}
+}
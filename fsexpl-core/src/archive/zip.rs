@@ -0,0 +1,129 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use super::{Member, u16_at, u32_at, u64_at, invalid};
+
+// the central directory at the end of the file lists every member with both sizes,
+// so nothing but the directory itself has to be read
+//
+//   end of central directory: signature, disk numbers, entry counts, directory size and offset, comment
+//   zip64 archives put a locator and a bigger record with 64 bit counts and offsets before it
+
+const END_SIGNATURE: u32 = 0x06054b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_END_SIGNATURE: u32 = 0x06064b50;
+const ENTRY_SIGNATURE: u32 = 0x02014b50;
+const END_SIZE: usize = 22;
+const MAX_COMMENT: usize = 0xffff;
+
+pub fn list<R: Read + Seek>(mut file: R) -> io::Result<Vec<Member>> {
+	let file_size = file.seek(SeekFrom::End(0))?;
+
+	// the end record is followed by a comment of up to 64K
+	let tail_size = file_size.min((END_SIZE + MAX_COMMENT) as u64);
+	let mut tail = vec![0; tail_size as usize];
+	file.seek(SeekFrom::Start(file_size - tail_size))?;
+	file.read_exact(&mut tail)?;
+
+	let end = (0..=tail.len().saturating_sub(END_SIZE)).rev()
+		.find(|&i| u32_at(&tail, i).is_ok_and(|s| s == END_SIGNATURE))
+		.ok_or_else(|| invalid("no zip end of central directory"))?;
+
+	let mut count = u16_at(&tail, end + 10)? as u64;
+	let mut directory_size = u32_at(&tail, end + 12)? as u64;
+	let mut directory_offset = u32_at(&tail, end + 16)? as u64;
+
+	if end >= 20 && u32_at(&tail, end - 20)? == ZIP64_LOCATOR_SIGNATURE {
+		let record_offset = u64_at(&tail, end - 12)?;
+		let mut record = [0; 56];
+		file.seek(SeekFrom::Start(record_offset))?;
+		file.read_exact(&mut record)?;
+		if u32_at(&record, 0)? != ZIP64_END_SIGNATURE {
+			return Err(invalid("bad zip64 end of central directory"));
+		}
+		count = u64_at(&record, 32)?;
+		directory_size = u64_at(&record, 40)?;
+		directory_offset = u64_at(&record, 48)?;
+	}
+
+	if directory_offset.saturating_add(directory_size) > file_size {
+		return Err(invalid("zip central directory outside the file"));
+	}
+	let mut directory = vec![0; directory_size as usize];
+	file.seek(SeekFrom::Start(directory_offset))?;
+	file.read_exact(&mut directory)?;
+
+	let mut members = Vec::new();
+	let mut at = 0;
+	for _ in 0..count {
+		if u32_at(&directory, at)? != ENTRY_SIGNATURE {
+			return Err(invalid("bad zip central directory entry"));
+		}
+
+		let made_by = u16_at(&directory, at + 4)?;
+		let time = u16_at(&directory, at + 12)?;
+		let date = u16_at(&directory, at + 14)?;
+		let mut compressed = u32_at(&directory, at + 20)? as u64;
+		let mut bytes = u32_at(&directory, at + 24)? as u64;
+		let name_length = u16_at(&directory, at + 28)? as usize;
+		let extra_length = u16_at(&directory, at + 30)? as usize;
+		let comment_length = u16_at(&directory, at + 32)? as usize;
+		let attributes = u32_at(&directory, at + 38)?;
+
+		let name_start = at + 46;
+		let extra_start = name_start + name_length;
+		let name = directory.get(name_start..extra_start).ok_or_else(|| invalid("truncated zip entry"))?;
+		let extra = directory.get(extra_start..extra_start + extra_length).ok_or_else(|| invalid("truncated zip entry"))?;
+		let path = String::from_utf8_lossy(name).replace('\\', "/");
+
+		let mut modified = dos_time(date, time);
+		let mut field = 0;
+		while field + 4 <= extra.len() {
+			let id = u16_at(extra, field)?;
+			let size = u16_at(extra, field + 2)? as usize;
+			let data = extra.get(field + 4..field + 4 + size).unwrap_or_default();
+			match id {
+				// zip64 sizes, only present for the fields that overflowed
+				0x0001 => {
+					let mut next = 0;
+					if bytes == 0xffffffff {
+						bytes = u64_at(data, next)?;
+						next += 8;
+					}
+					if compressed == 0xffffffff {
+						compressed = u64_at(data, next)?;
+					}
+				},
+				// extended timestamp with the modification time in unix seconds
+				0x5455 if data.len() >= 5 && data[0] & 1 != 0 => modified = u32_at(data, 1)? as i32 as i64,
+				_ => {},
+			}
+			field += 4 + size;
+		}
+
+		// the upper half of the external attributes holds the unix mode for archives made on unix
+		let mode = if made_by >> 8 == 3 { attributes >> 16 } else { 0 };
+		let is_dir = path.ends_with('/') || mode & 0o170000 == 0o040000 || attributes & 0x10 != 0;
+
+		members.push(Member { path, is_dir, bytes, compressed, modified, mode });
+		at = extra_start + extra_length + comment_length;
+	}
+
+	Ok(members)
+}
+
+// ms-dos date and time fields, taken as utc since the zone isn't stored
+fn dos_time(date: u16, time: u16) -> i64 {
+	let year = 1980 + (date >> 9) as i64;
+	let month = ((date >> 5) & 0xf).clamp(1, 12) as i64;
+	let day = (date & 0x1f).max(1) as i64;
+
+	// days from the civil date, counting march as the first month
+	let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+	let era = y.div_euclid(400);
+	let year_of_era = y - era * 400;
+	let day_of_year = (153 * m + 2) / 5 + day - 1;
+	let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+	let days = era * 146097 + day_of_era - 719468;
+
+	days * 86400 + (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3f) as i64 * 60 + (time & 0x1f) as i64 * 2
+}
@@ -0,0 +1,161 @@
+use std::{io::{self, Write}, collections::HashMap, fs};
+
+use crate::{node::{Entry, TreeNode}, export::Options, text::time_to_text};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Column {
+	Path,
+	Depth, // levels below the exported root
+	Bytes,
+	Allocated,
+	Files,
+	Extension,
+	Modified,
+	Owner,
+}
+
+impl Column {
+	pub const ALL: [Column; 8] = [Column::Path, Column::Depth, Column::Bytes, Column::Allocated, Column::Files, Column::Extension, Column::Modified, Column::Owner];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Column::Path => "path",
+			Column::Depth => "depth",
+			Column::Bytes => "bytes",
+			Column::Allocated => "allocated",
+			Column::Files => "files",
+			Column::Extension => "extension",
+			Column::Modified => "modified",
+			Column::Owner => "owner",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|c| c.name() == name)
+	}
+}
+
+// which entries get a row
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Rows {
+	All,
+	Files,
+	Directories,
+}
+
+impl Rows {
+	pub const ALL: [Rows; 3] = [Rows::All, Rows::Files, Rows::Directories];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Rows::All => "all",
+			Rows::Files => "files",
+			Rows::Directories => "directories",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|r| r.name() == name)
+	}
+
+	fn includes(self, entry: &Entry) -> bool {
+		match self {
+			Rows::All => true,
+			Rows::Files => !entry.is_dir(),
+			Rows::Directories => entry.is_dir(),
+		}
+	}
+}
+
+// one row per entry that passes the filter, written while walking the tree
+// csv fields are quoted as in RFC 4180, tsv fields escape tabs, line breaks and backslashes as \t, \n, \r and \\
+// owners are uids unless the options ask for names
+pub fn write<N: TreeNode>(root: &N, options: &Options, separator: u8, writer: &mut impl Write) -> io::Result<()> {
+	let owners = if options.owner_names { user_names() } else { HashMap::new() };
+	let table = Table { options, separator, owners };
+
+	let header: Vec<&str> = options.columns.iter().map(|c| c.name()).collect();
+	table.write_row(writer, &header)?;
+	table.write_node(root, 0, writer)
+}
+
+struct Table<'a> {
+	options: &'a Options,
+	separator: u8,
+	owners: HashMap<u32, String>,
+}
+
+impl Table<'_> {
+	fn write_node<N: TreeNode>(&self, node: &N, level: usize, writer: &mut impl Write) -> io::Result<()> {
+		let entry = node.entry();
+		if !self.options.filter.includes(entry, level) {
+			return Ok(());
+		}
+
+		if self.options.rows.includes(entry) {
+			let fields: Vec<String> = self.options.columns.iter().map(|&c| self.field(entry, c, level)).collect();
+			self.write_row(writer, &fields)?;
+		}
+
+		if self.options.filter.descends(level) {
+			for child in node.children() {
+				self.write_node(child, level + 1, writer)?;
+			}
+		}
+
+		Ok(())
+	}
+
+	fn field(&self, entry: &Entry, column: Column, level: usize) -> String {
+		let metadata = entry.metadata();
+		match column {
+			Column::Path => entry.get_full_path(),
+			Column::Depth => level.to_string(),
+			Column::Bytes => entry.bytes().to_string(),
+			Column::Allocated => (metadata.blocks * 512).to_string(),
+			Column::Files => entry.files().to_string(),
+			Column::Extension => entry.extension().to_string(),
+			Column::Modified => time_to_text(metadata.modified),
+			Column::Owner => self.owners.get(&metadata.uid).cloned().unwrap_or_else(|| metadata.uid.to_string()),
+		}
+	}
+
+	fn write_row<S: AsRef<str>>(&self, writer: &mut impl Write, fields: &[S]) -> io::Result<()> {
+		for (i, field) in fields.iter().enumerate() {
+			if i > 0 {
+				writer.write_all(&[self.separator])?;
+			}
+			writer.write_all(self.escape(field.as_ref()).as_bytes())?;
+		}
+		writer.write_all(b"\n")
+	}
+
+	fn escape(&self, field: &str) -> String {
+		if self.separator == b'\t' {
+			return field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r");
+		}
+
+		if field.contains([',', '"', '\n', '\r']) {
+			format!("\"{}\"", field.replace('"', "\"\""))
+		}
+		else {
+			field.to_string()
+		}
+	}
+}
+
+// uid to user name from /etc/passwd, empty where there is none
+fn user_names() -> HashMap<u32, String> {
+	let Ok(passwd) = fs::read_to_string("/etc/passwd") else {
+		return HashMap::new();
+	};
+
+	passwd.lines()
+		.filter_map(|line| {
+			let mut fields = line.split(':');
+			let name = fields.next()?;
+			let uid = fields.nth(1)?.parse().ok()?;
+			Some((uid, name.to_string()))
+		})
+		.collect()
+}
@@ -0,0 +1,144 @@
+use std::{fs::File, io::{self, Read, BufRead, BufReader}, path::{Path, PathBuf}, collections::HashMap};
+
+use crate::{node::{Node, Entry}, metadata::Metadata};
+
+// what the sizes in a du listing count
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Units {
+	#[default]
+	Bytes, // du -ab
+	Kilobytes, // du -ak
+}
+
+// where directory sizes come from
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DirSizes {
+	#[default]
+	Children, // the sum of the listed entries below
+	Listed, // the listed totals, with the difference to the children shown as an "(unlisted)" entry
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Import {
+	pub units: Units,
+	pub dir_sizes: DirSizes,
+}
+
+const UNLISTED: &str = "(unlisted)";
+
+pub fn load(path: &Path, import: Import) -> io::Result<Node> {
+	read(BufReader::new(File::open(path)?), import)
+}
+
+// true if the first lines of the file are sizes and paths separated by a tab
+pub fn is_du(path: &Path) -> bool {
+	let Ok(file) = File::open(path) else {
+		return false;
+	};
+	if !path.is_file() {
+		return false;
+	}
+
+	let mut lines = BufReader::new(file.take(4096)).lines().take(5).peekable();
+	lines.peek().is_some() && lines.all(|line| line.is_ok_and(|line| parse_line(&line).is_some()))
+}
+
+// the lines of du -a, "size<tab>path", in any order
+// paths that don't appear themselves but have listed descendants become directories, and so do
+// listed paths with descendants, everything else becomes a file (empty directories included, du doesn't tell)
+pub fn read(reader: impl BufRead, import: Import) -> io::Result<Node> {
+	let unit = match import.units {
+		Units::Bytes => 1,
+		Units::Kilobytes => 1024,
+	};
+
+	let mut sizes = HashMap::new();
+	for (number, line) in reader.lines().enumerate() {
+		let line = line?;
+		if line.is_empty() {
+			continue;
+		}
+		let (size, path) = parse_line(&line).ok_or_else(|| invalid(&format!("line {} is not a du line", number + 1)))?;
+		sizes.insert(PathBuf::from(path), size * unit);
+	}
+
+	let root = common_ancestor(sizes.keys()).ok_or_else(|| invalid("empty du listing"))?;
+
+	// every path under its parent, creating the ancestors du didn't list
+	let mut children: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+	for path in sizes.keys() {
+		let mut path = path.as_path();
+		while path != root {
+			let Some(parent) = path.parent() else {
+				break;
+			};
+			let siblings = children.entry(parent.to_owned()).or_default();
+			let known = !siblings.is_empty() || sizes.contains_key(parent);
+			siblings.push(path.to_owned());
+			if known {
+				break;
+			}
+			path = parent;
+		}
+	}
+	for siblings in children.values_mut() {
+		siblings.sort_unstable();
+		siblings.dedup();
+	}
+
+	Ok(build(&root, &sizes, &children, import.dir_sizes))
+}
+
+fn build(path: &Path, sizes: &HashMap<PathBuf, u64>, children: &HashMap<PathBuf, Vec<PathBuf>>, dir_sizes: DirSizes) -> Node {
+	let listed = sizes.get(path).copied();
+
+	let Some(child_paths) = children.get(path) else {
+		let entry = Entry::new(path, false, listed.unwrap_or(0), Metadata { mode: 0o100644, ..Default::default() });
+		return Node::file(entry);
+	};
+
+	let mut nodes: Vec<Node> = child_paths.iter().map(|child| build(child, sizes, children, dir_sizes)).collect();
+
+	if dir_sizes == DirSizes::Listed {
+		let sum: u64 = nodes.iter().map(|n| n.entry.bytes()).sum();
+		if let Some(rest) = listed.and_then(|total| total.checked_sub(sum)).filter(|&rest| rest > 0) {
+			let metadata = Metadata { mode: 0o100644, ..Default::default() };
+			nodes.push(Node::file(Entry::new(&path.join(UNLISTED), false, rest, metadata)));
+		}
+	}
+
+	nodes.sort_unstable_by_key(|n| n.entry.bytes());
+
+	let metadata = Metadata { mode: 0o040755, ..Default::default() };
+	Node::directory(Entry::new(path, true, 0, metadata), nodes)
+}
+
+fn parse_line(line: &str) -> Option<(u64, &str)> {
+	let (size, path) = line.split_once('\t')?;
+	let size = size.trim().parse().ok()?;
+	(!path.is_empty()).then_some((size, path))
+}
+
+// the deepest path that is or contains every path
+fn common_ancestor<'a>(paths: impl Iterator<Item = &'a PathBuf>) -> Option<PathBuf> {
+	let mut ancestor: Option<PathBuf> = None;
+
+	for path in paths {
+		ancestor = Some(match ancestor {
+			None => path.clone(),
+			Some(ancestor) => {
+				let mut common = ancestor.as_path();
+				while !path.starts_with(common) {
+					common = common.parent()?;
+				}
+				common.to_owned()
+			},
+		});
+	}
+
+	ancestor
+}
+
+fn invalid(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
@@ -0,0 +1,78 @@
+use std::io::{self, Write};
+
+use crate::{node::{Entry, TreeNode}, json, csv::{self, Column, Rows}, ncdu, html};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+	Json,
+	Csv,
+	Tsv,
+	Ncdu, // readable by ncdu -f
+	Html, // an interactive treemap in a single page
+}
+
+impl Format {
+	pub const ALL: [Format; 5] = [Format::Json, Format::Csv, Format::Tsv, Format::Ncdu, Format::Html];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Format::Json => "json",
+			Format::Csv => "csv",
+			Format::Tsv => "tsv",
+			Format::Ncdu => "ncdu",
+			Format::Html => "html",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|f| f.name() == name)
+	}
+}
+
+// which entries an export includes, the root always is
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Filter {
+	pub max_depth: Option<usize>, // levels below the root
+	pub min_bytes: u64,
+}
+
+impl Filter {
+	pub fn includes(&self, entry: &Entry, level: usize) -> bool {
+		level == 0 || (self.max_depth.is_none_or(|max| level <= max) && entry.bytes() >= self.min_bytes)
+	}
+
+	// true if the children of an entry at this level can be included
+	pub fn descends(&self, level: usize) -> bool {
+		self.max_depth.is_none_or(|max| level < max)
+	}
+}
+
+// the columns and rows only apply to the flat formats
+#[derive(Clone, Debug)]
+pub struct Options {
+	pub filter: Filter,
+	pub columns: Vec<Column>,
+	pub rows: Rows,
+	pub owner_names: bool, // uids looked up in the local /etc/passwd, only right for scans of the local disk
+}
+
+impl Default for Options {
+	fn default() -> Self {
+		Self {
+			filter: Filter::default(),
+			columns: Column::ALL.to_vec(),
+			rows: Rows::All,
+			owner_names: false,
+		}
+	}
+}
+
+pub fn write<N: TreeNode>(root: &N, format: Format, options: &Options, writer: &mut impl Write) -> io::Result<()> {
+	match format {
+		Format::Json => json::write(root, &options.filter, writer),
+		Format::Csv => csv::write(root, options, b',', writer),
+		Format::Tsv => csv::write(root, options, b'\t', writer),
+		Format::Ncdu => ncdu::write(root, &options.filter, writer),
+		Format::Html => html::write(root, &options.filter, writer),
+	}
+}
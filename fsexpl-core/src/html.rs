@@ -0,0 +1,76 @@
+use std::io::{self, Write};
+
+use crate::{node::TreeNode, export::Filter, json};
+
+// a single page with the tree embedded as JSON and a small SVG treemap drawn by the script in it,
+// so it can be attached anywhere and opened without fsexpl or a network connection
+const TEMPLATE: &str = include_str!("treemap.html");
+
+// every node has n (name), b (bytes), f (files), d (dirs), k (kind) and m (modified), the root also p (full path)
+// directories within the depth limit have c (children), plus o ([count, bytes]) when some were left out
+pub fn write<N: TreeNode>(root: &N, filter: &Filter, writer: &mut impl Write) -> io::Result<()> {
+	let (head, tail) = TEMPLATE.split_once("/* data */").expect("the template has a data marker");
+
+	writer.write_all(head.as_bytes())?;
+	write_node(root, filter, 0, writer)?;
+	writer.write_all(tail.as_bytes())
+}
+
+fn write_node<N: TreeNode>(node: &N, filter: &Filter, level: usize, writer: &mut impl Write) -> io::Result<()> {
+	let entry = node.entry();
+
+	writer.write_all(b"{\"n\":")?;
+	write_string(writer, entry.name())?;
+	if level == 0 {
+		writer.write_all(b",\"p\":")?;
+		write_string(writer, &entry.get_full_path())?;
+	}
+	write!(
+		writer,
+		",\"b\":{},\"f\":{},\"d\":{},\"k\":\"{}\",\"m\":{}",
+		entry.bytes(), entry.files(), entry.dirs(), entry.kind(), entry.metadata().modified,
+	)?;
+
+	if entry.is_dir() && filter.descends(level) {
+		let mut omitted = (0, 0);
+
+		writer.write_all(b",\"c\":[")?;
+		let mut first = true;
+		for child in node.children() {
+			if !filter.includes(child.entry(), level + 1) {
+				omitted.0 += 1;
+				omitted.1 += child.entry().bytes();
+				continue;
+			}
+			if !first {
+				writer.write_all(b",")?;
+			}
+			first = false;
+			write_node(child, filter, level + 1, writer)?;
+		}
+		writer.write_all(b"]")?;
+
+		if omitted.0 > 0 {
+			write!(writer, ",\"o\":[{},{}]", omitted.0, omitted.1)?;
+		}
+	}
+
+	writer.write_all(b"}")
+}
+
+// a JSON string that can't end the script it is in, whatever the file names are
+fn write_string(writer: &mut impl Write, text: &str) -> io::Result<()> {
+	let mut quoted = Vec::new();
+	json::write_string(&mut quoted, text)?;
+
+	for part in quoted.split_inclusive(|&b| b == b'<') {
+		match part.strip_suffix(b"<") {
+			Some(before) => {
+				writer.write_all(before)?;
+				writer.write_all(b"\\u003c")?;
+			},
+			None => writer.write_all(part)?,
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,263 @@
+use std::io::{self, Write, BufRead};
+
+use crate::{node::TreeNode, export::Filter};
+
+// writes the tree as nested JSON objects while walking it, so nothing is built in memory
+//
+// every node has name, path, kind, bytes, allocated, files, dirs, depth and metadata, which has storage_class for objects in buckets
+// directories within the depth limit also have children, listing those that pass the filter,
+// plus omitted_children and omitted_bytes when some were left out
+pub fn write<N: TreeNode>(root: &N, filter: &Filter, writer: &mut impl Write) -> io::Result<()> {
+	write_node(root, filter, 0, writer)?;
+	writer.write_all(b"\n")
+}
+
+fn write_node<N: TreeNode>(node: &N, filter: &Filter, level: usize, writer: &mut impl Write) -> io::Result<()> {
+	let entry = node.entry();
+	let m = entry.metadata();
+
+	writer.write_all(b"{\"name\":")?;
+	write_string(writer, entry.name())?;
+	writer.write_all(b",\"path\":")?;
+	write_string(writer, &entry.get_full_path())?;
+	write!(
+		writer,
+		",\"kind\":\"{}\",\"bytes\":{},\"allocated\":{},\"files\":{},\"dirs\":{},\"depth\":{}",
+		entry.kind(), entry.bytes(), m.blocks * 512, entry.files(), entry.dirs(), entry.depth(),
+	)?;
+	write!(
+		writer,
+		",\"metadata\":{{\"mode\":\"{}\",\"uid\":{},\"gid\":{},\"modified\":{},\"inode\":{},\"device\":{}",
+		m.mode_text(), m.uid, m.gid, m.modified, m.inode, m.device,
+	)?;
+	if let Some(class) = m.storage_class {
+		write!(writer, ",\"storage_class\":\"{}\"", class.name())?;
+	}
+	writer.write_all(b"}")?;
+
+	if entry.is_dir() && filter.descends(level) {
+		let mut omitted = (0, 0);
+
+		writer.write_all(b",\"children\":[")?;
+		let mut first = true;
+		for child in node.children() {
+			if !filter.includes(child.entry(), level + 1) {
+				omitted.0 += 1;
+				omitted.1 += child.entry().bytes();
+				continue;
+			}
+			if !first {
+				writer.write_all(b",")?;
+			}
+			first = false;
+			write_node(child, filter, level + 1, writer)?;
+		}
+		writer.write_all(b"]")?;
+
+		if omitted.0 > 0 {
+			write!(writer, ",\"omitted_children\":{},\"omitted_bytes\":{}", omitted.0, omitted.1)?;
+		}
+	}
+
+	writer.write_all(b"}")
+}
+
+// a quoted JSON string
+pub fn write_string(writer: &mut impl Write, text: &str) -> io::Result<()> {
+	writer.write_all(b"\"")?;
+
+	let mut start = 0;
+	for (i, ch) in text.char_indices() {
+		let escaped = match ch {
+			'"' => "\\\"",
+			'\\' => "\\\\",
+			'\n' => "\\n",
+			'\r' => "\\r",
+			'\t' => "\\t",
+			c if (c as u32) < 0x20 => "",
+			_ => continue,
+		};
+
+		writer.write_all(&text.as_bytes()[start..i])?;
+		if escaped.is_empty() {
+			write!(writer, "\\u{:04x}", ch as u32)?;
+		}
+		else {
+			writer.write_all(escaped.as_bytes())?;
+		}
+		start = i + ch.len_utf8();
+	}
+
+	writer.write_all(&text.as_bytes()[start..])?;
+	writer.write_all(b"\"")
+}
+
+// a pull parser for reading big documents straight into other structures
+pub struct Parser<R: BufRead> {
+	reader: R,
+}
+
+impl<R: BufRead> Parser<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader }
+	}
+
+	// the next byte that isn't whitespace, without consuming it
+	pub fn peek(&mut self) -> io::Result<u8> {
+		loop {
+			let buffer = self.reader.fill_buf()?;
+			let Some(&byte) = buffer.first() else {
+				return Err(invalid("unexpected end of JSON"));
+			};
+			if !byte.is_ascii_whitespace() {
+				return Ok(byte);
+			}
+			self.reader.consume(1);
+		}
+	}
+
+	pub fn expect(&mut self, expected: u8) -> io::Result<()> {
+		if self.peek()? != expected {
+			return Err(invalid(&format!("expected {} in JSON", expected as char)));
+		}
+		self.reader.consume(1);
+		Ok(())
+	}
+
+	// after an array element or object field: true if another one follows, false at the closing bracket
+	pub fn next_or_end(&mut self, end: u8) -> io::Result<bool> {
+		match self.peek()? {
+			b',' => {
+				self.reader.consume(1);
+				Ok(true)
+			},
+			byte if byte == end => {
+				self.reader.consume(1);
+				Ok(false)
+			},
+			_ => Err(invalid("expected , in JSON")),
+		}
+	}
+
+	// calls the closure with every key, which has to read or skip the value
+	pub fn object(&mut self, mut field: impl FnMut(&mut Self, String) -> io::Result<()>) -> io::Result<()> {
+		self.expect(b'{')?;
+		if self.peek()? == b'}' {
+			self.reader.consume(1);
+			return Ok(());
+		}
+
+		loop {
+			let key = self.string()?;
+			self.expect(b':')?;
+			field(self, key)?;
+			if !self.next_or_end(b'}')? {
+				return Ok(());
+			}
+		}
+	}
+
+	pub fn string(&mut self) -> io::Result<String> {
+		self.expect(b'"')?;
+		let mut bytes = Vec::new();
+
+		loop {
+			match self.byte()? {
+				b'"' => break,
+				b'\\' => match self.byte()? {
+					b'n' => bytes.push(b'\n'),
+					b't' => bytes.push(b'\t'),
+					b'r' => bytes.push(b'\r'),
+					b'b' => bytes.push(0x08),
+					b'f' => bytes.push(0x0c),
+					b'u' => {
+						let mut code = self.hex4()?;
+						// a surrogate pair for characters outside the basic plane
+						if (0xd800..0xdc00).contains(&code) && self.byte()? == b'\\' && self.byte()? == b'u' {
+							code = 0x10000 + ((code - 0xd800) << 10) + (self.hex4()?.wrapping_sub(0xdc00) & 0x3ff);
+						}
+						let ch = char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER);
+						bytes.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+					},
+					other => bytes.push(other),
+				},
+				byte => bytes.push(byte),
+			}
+		}
+
+		Ok(String::from_utf8_lossy(&bytes).into_owned())
+	}
+
+	pub fn number(&mut self) -> io::Result<f64> {
+		self.peek()?;
+		let mut text = String::new();
+		while let Some(&byte) = self.reader.fill_buf()?.first() {
+			if !(byte.is_ascii_digit() || matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E')) {
+				break;
+			}
+			text.push(byte as char);
+			self.reader.consume(1);
+		}
+		text.parse().map_err(|_| invalid("expected a number in JSON"))
+	}
+
+	pub fn boolean(&mut self) -> io::Result<bool> {
+		let value = self.peek()? == b't';
+		self.literal(if value { "true" } else { "false" })?;
+		Ok(value)
+	}
+
+	pub fn skip_value(&mut self) -> io::Result<()> {
+		match self.peek()? {
+			b'{' => self.object(|parser, _| parser.skip_value()),
+			b'[' => {
+				self.expect(b'[')?;
+				if self.peek()? == b']' {
+					self.reader.consume(1);
+					return Ok(());
+				}
+				loop {
+					self.skip_value()?;
+					if !self.next_or_end(b']')? {
+						return Ok(());
+					}
+				}
+			},
+			b'"' => self.string().map(|_| ()),
+			b't' | b'f' => self.boolean().map(|_| ()),
+			b'n' => self.literal("null"),
+			_ => self.number().map(|_| ()),
+		}
+	}
+
+	fn literal(&mut self, literal: &str) -> io::Result<()> {
+		self.peek()?;
+		for expected in literal.bytes() {
+			if self.byte()? != expected {
+				return Err(invalid("unexpected literal in JSON"));
+			}
+		}
+		Ok(())
+	}
+
+	fn byte(&mut self) -> io::Result<u8> {
+		let Some(&byte) = self.reader.fill_buf()?.first() else {
+			return Err(invalid("unexpected end of JSON"));
+		};
+		self.reader.consume(1);
+		Ok(byte)
+	}
+
+	fn hex4(&mut self) -> io::Result<u32> {
+		let mut code = 0;
+		for _ in 0..4 {
+			let digit = (self.byte()? as char).to_digit(16).ok_or_else(|| invalid("bad \\u escape in JSON"))?;
+			code = code * 16 + digit;
+		}
+		Ok(code)
+	}
+}
+
+fn invalid(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
@@ -0,0 +1,167 @@
+// the treemap layout, shared by the window and everything that draws a tree without one
+
+use crate::{node::{Entry, TreeNode, Weight}, export::Filter};
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Rect {
+	pub x: f32,
+	pub y: f32,
+	pub w: f32,
+	pub h: f32,
+}
+
+impl Rect {
+	pub fn new(x: f32, y: f32, w: f32, h: f32) -> Self {
+		Self { x, y, w, h }
+	}
+
+	// the area a directory leaves for its children, inside a frame of 5% of the shorter side
+	pub fn inner(self) -> Self {
+		let margin = 0.05 * self.w.min(self.h);
+		Self::new(self.x + margin, self.y + margin, self.w - 2.0 * margin, self.h - 2.0 * margin)
+	}
+
+	pub fn contains(self, x: f32, y: f32) -> bool {
+		x >= self.x && x < self.x + self.w && y >= self.y && y < self.y + self.h
+	}
+}
+
+// one rectangle per weight, in the same order, together covering the given one
+// the weights are halved as evenly as possible and the rectangle cut across its longer side,
+// then both halves are split again, so sorting the weights keeps the big ones together
+pub fn split(weights: &[u64], rect: Rect) -> Vec<Rect> {
+	let mut rects = vec![rect; weights.len()];
+	place(weights, &mut rects, rect);
+	rects
+}
+
+fn place(weights: &[u64], rects: &mut [Rect], rect: Rect) {
+	// end condition - give all space if only one weight is left
+	if weights.len() <= 1 {
+		if let Some(last) = rects.first_mut() {
+			*last = rect;
+		}
+		return;
+	}
+
+	let size_sum: u64 = weights.iter().sum();
+
+	let mut half_sum = 0;
+	let mut split_index = 0;
+
+	// find the index where [0, split_index) and [split_index, length) are as evenly split as possible
+	for &weight in weights {
+		half_sum += weight;
+		split_index += 1;
+		if half_sum > size_sum / 2 {
+			break;
+		}
+	}
+
+	// correct the split_index if overshoot
+	if ((size_sum / 2) as i128 - half_sum as i128).abs() >=
+	   ((size_sum / 2) as i128 - (half_sum as i128 - weights[split_index - 1] as i128)).abs() {
+		split_index -= 1;
+		half_sum -= weights[split_index];
+	}
+
+	// keeps both halves non-empty, which also prevents infinite recursion
+	if split_index == 0 {
+		split_index = 1;
+		half_sum = weights[0];
+	}
+	if split_index == weights.len() {
+		split_index = weights.len() - 1;
+		half_sum = size_sum - weights[weights.len() - 1];
+	}
+
+	// split the rectangle, by count when everything weighs nothing
+	let proportion = if size_sum == 0 {
+		split_index as f32 / weights.len() as f32
+	}
+	else {
+		half_sum as f32 / size_sum as f32
+	};
+	let mut rect1 = rect;
+	let mut rect2 = rect;
+
+	if rect.w > rect.h {
+		rect1.w *= proportion;
+		rect2.w *= 1.0 - proportion;
+		rect2.x += rect1.w;
+	}
+	else {
+		rect1.h *= proportion;
+		rect2.h *= 1.0 - proportion;
+		rect2.y += rect1.h;
+	}
+
+	// divide further
+	let (weights1, weights2) = weights.split_at(split_index);
+	let (rects1, rects2) = rects.split_at_mut(split_index);
+	place(weights1, rects1, rect1);
+	place(weights2, rects2, rect2);
+}
+
+// what a tile shows
+#[derive(Clone, Copy, Debug)]
+pub enum Content<'a> {
+	Entry(&'a Entry),
+	Omitted { count: usize, bytes: u64 }, // the children the filter left out of a directory, together
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tile<'a> {
+	pub content: Content<'a>,
+	pub rect: Rect,
+	pub level: usize,
+	pub expanded: bool, // a directory drawn as a frame around its children
+}
+
+// the whole tree laid out in the rectangle, parents before their children so they can be painted in order
+// directories are expanded as deep as the filter goes while their inner area is at least min_side on both sides
+pub fn tiles<'a, N: TreeNode>(root: &'a N, filter: &Filter, weight: Weight, rect: Rect, min_side: f32) -> Vec<Tile<'a>> {
+	let mut tiles = Vec::new();
+	add_tiles(root, filter, weight, rect, min_side, 0, &mut tiles);
+	tiles
+}
+
+fn add_tiles<'a, N: TreeNode>(node: &'a N, filter: &Filter, weight: Weight, rect: Rect, min_side: f32, level: usize, tiles: &mut Vec<Tile<'a>>) {
+	let inner = rect.inner();
+	let mut children: Vec<&N> = Vec::new();
+	let mut omitted = (0, 0, 0); // count, bytes, weight
+
+	if filter.descends(level) && inner.w >= min_side && inner.h >= min_side {
+		for child in node.children() {
+			if filter.includes(child.entry(), level + 1) {
+				children.push(child);
+			}
+			else {
+				omitted.0 += 1;
+				omitted.1 += child.entry().bytes();
+				omitted.2 += child.entry().weight(weight);
+			}
+		}
+	}
+
+	let expanded = !children.is_empty();
+	tiles.push(Tile { content: Content::Entry(node.entry()), rect, level, expanded });
+	if !expanded {
+		return;
+	}
+
+	// the left out children take up the space they would have, sorted in among the others
+	let mut parts: Vec<(u64, Option<&N>)> = children.into_iter().map(|n| (n.entry().weight(weight), Some(n))).collect();
+	if omitted.0 > 0 {
+		parts.push((omitted.2, None));
+	}
+	parts.sort_unstable_by_key(|part| part.0);
+
+	let weights: Vec<u64> = parts.iter().map(|part| part.0).collect();
+	for ((_, child), rect) in parts.into_iter().zip(split(&weights, inner)) {
+		match child {
+			Some(child) => add_tiles(child, filter, weight, rect, min_side, level + 1, tiles),
+			None => tiles.push(Tile { content: Content::Omitted { count: omitted.0, bytes: omitted.1 }, rect, level: level + 1, expanded: false }),
+		}
+	}
+}
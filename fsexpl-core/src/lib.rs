@@ -0,0 +1,22 @@
+// the filesystem model without any rendering: scanning, the tree with its aggregates, and snapshots
+
+pub mod metadata;
+pub mod node;
+pub mod scan;
+pub mod snapshot;
+pub mod text;
+pub mod export;
+pub mod json;
+pub mod csv;
+pub mod ncdu;
+pub mod du;
+pub mod html;
+pub mod layout;
+pub mod archive;
+pub mod source;
+pub mod sftp;
+pub mod s3;
+
+pub use node::{Node, Entry, TreeNode, Weight};
+pub use scan::{ScanOptions, SizeMode};
+pub use source::FileSource;
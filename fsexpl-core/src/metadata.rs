@@ -0,0 +1,125 @@
+use std::fs;
+
+// the subset of fs::Metadata kept for every node, the modification time in seconds since the unix epoch
+// access and change times aren't kept, a tree holds one of these per file
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Metadata {
+	pub modified: i64,
+	pub blocks: u64, // 512 byte blocks actually allocated
+	pub inode: u64,
+	pub device: u64,
+	pub mode: u32,
+	pub uid: u32,
+	pub gid: u32,
+	pub storage_class: Option<StorageClass>, // objects in buckets
+}
+
+impl Metadata {
+	#[cfg(unix)]
+	pub fn from_fs(metadata: &fs::Metadata) -> Self {
+		use std::os::unix::fs::MetadataExt;
+
+		Self {
+			modified: metadata.mtime(),
+			mode: metadata.mode(),
+			uid: metadata.uid(),
+			gid: metadata.gid(),
+			inode: metadata.ino(),
+			device: metadata.dev(),
+			blocks: metadata.blocks(),
+			storage_class: None,
+		}
+	}
+
+	#[cfg(not(unix))]
+	pub fn from_fs(metadata: &fs::Metadata) -> Self {
+		use std::time::{SystemTime, UNIX_EPOCH};
+
+		let seconds = |time: std::io::Result<SystemTime>| {
+			time.ok()
+				.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+				.map(|d| d.as_secs() as i64)
+				.unwrap_or(0)
+		};
+
+		Self {
+			modified: seconds(metadata.modified()),
+			mode: if metadata.permissions().readonly() { 0o444 } else { 0o644 },
+			blocks: (metadata.len() + 511) / 512,
+			..Default::default()
+		}
+	}
+
+	// sources that don't report a type leave the mode 0, their entries count as regular files
+	pub fn is_regular(&self) -> bool {
+		matches!(self.mode & 0o170000, 0o100000 | 0)
+	}
+
+	pub fn mode_text(&self) -> String {
+		let kind = match self.mode & 0o170000 {
+			0o040000 => 'd',
+			0o120000 => 'l',
+			0o020000 => 'c',
+			0o060000 => 'b',
+			0o010000 => 'p',
+			0o140000 => 's',
+			_ => '-',
+		};
+
+		let mut text = String::with_capacity(10);
+		text.push(kind);
+		for shift in [6, 3, 0] {
+			let bits = (self.mode >> shift) & 0o7;
+			text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+			text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+			text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+		}
+		text
+	}
+}
+
+// the storage classes of s3 objects
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StorageClass {
+	Standard,
+	ReducedRedundancy,
+	StandardIa,
+	OnezoneIa,
+	IntelligentTiering,
+	GlacierIr,
+	Glacier,
+	DeepArchive,
+	ExpressOnezone,
+	Other, // classes of other stores, or newer ones
+}
+
+const STORAGE_CLASSES: [(&str, StorageClass); 9] = [
+	("STANDARD", StorageClass::Standard),
+	("REDUCED_REDUNDANCY", StorageClass::ReducedRedundancy),
+	("STANDARD_IA", StorageClass::StandardIa),
+	("ONEZONE_IA", StorageClass::OnezoneIa),
+	("INTELLIGENT_TIERING", StorageClass::IntelligentTiering),
+	("GLACIER_IR", StorageClass::GlacierIr),
+	("GLACIER", StorageClass::Glacier),
+	("DEEP_ARCHIVE", StorageClass::DeepArchive),
+	("EXPRESS_ONEZONE", StorageClass::ExpressOnezone),
+];
+
+impl StorageClass {
+	pub fn from_name(name: &str) -> Self {
+		STORAGE_CLASSES.iter().find(|(n, _)| *n == name).map_or(Self::Other, |(_, class)| *class)
+	}
+
+	pub fn name(&self) -> &'static str {
+		STORAGE_CLASSES.iter().find(|(_, c)| c == self).map_or("other", |(name, _)| name)
+	}
+
+	// the number snapshots store, 0 is left for entries without a class
+	pub fn number(&self) -> u8 {
+		STORAGE_CLASSES.iter().position(|(_, c)| c == self).unwrap_or(STORAGE_CLASSES.len()) as u8 + 1
+	}
+
+	pub fn from_number(number: u8) -> Option<Self> {
+		number.checked_sub(1).map(|index| STORAGE_CLASSES.get(index as usize).map_or(Self::Other, |(_, class)| *class))
+	}
+}
@@ -0,0 +1,225 @@
+use std::{fs::File, io::{self, Read, Write, BufRead, BufReader, BufWriter}, path::Path, time::{SystemTime, UNIX_EPOCH}, collections::HashSet};
+
+use crate::{node::{Node, Entry, TreeNode}, metadata::Metadata, export::Filter, json::{self, Parser}, scan::SizeMode};
+
+// the JSON dump of ncdu -o, https://dev.yorhel.nl/ncdu/jsonfmt
+//
+//   [1, 2, {header}, directory]
+//   directory: [{info}, file or directory...]
+//   file: {info}
+//
+// info has name (the full path for the root), asize and dsize (apparent and disk bytes), and optionally
+// ino, dev (when it differs from the parent), hlnkc (for files with more than one link), uid, gid, mode, mtime,
+// notreg, read_error and excluded
+
+const MAJOR_VERSION: u32 = 1;
+const MINOR_VERSION: u32 = 2;
+
+pub fn load(path: &Path, size_mode: SizeMode) -> io::Result<Node> {
+	read(BufReader::new(File::open(path)?), size_mode)
+}
+
+pub fn save<N: TreeNode>(root: &N, filter: &Filter, path: &Path) -> io::Result<()> {
+	let mut writer = BufWriter::new(File::create(path)?);
+	write(root, filter, &mut writer)?;
+	writer.flush()
+}
+
+// true if the file starts like an ncdu dump
+pub fn is_ncdu(path: &Path) -> bool {
+	let Ok(file) = File::open(path) else {
+		return false;
+	};
+
+	let mut start = Vec::new();
+	if !path.is_file() || file.take(64).read_to_end(&mut start).is_err() {
+		return false;
+	}
+
+	let compact: Vec<u8> = start.into_iter().filter(|b| !b.is_ascii_whitespace()).collect();
+	compact.starts_with(format!("[{MAJOR_VERSION},").as_bytes())
+}
+
+// entries are sized by asize or dsize, hardlinked files count once like ncdu counts them
+pub fn read(reader: impl BufRead, size_mode: SizeMode) -> io::Result<Node> {
+	let mut parser = Parser::new(reader);
+	let mut loader = Loader { size_mode, links: HashSet::new() };
+
+	parser.expect(b'[')?;
+	if parser.number()? as u32 != MAJOR_VERSION {
+		return Err(invalid("unsupported ncdu version"));
+	}
+	parser.expect(b',')?;
+	parser.number()?;
+	parser.expect(b',')?;
+	parser.skip_value()?;
+	parser.expect(b',')?;
+
+	let root = loader.read_item(&mut parser, None, 0)?.ok_or_else(|| invalid("the root is excluded"))?;
+
+	// anything after the tree is left alone
+	Ok(root)
+}
+
+struct Loader {
+	size_mode: SizeMode,
+	links: HashSet<(u64, u64)>, // device and inode of the hardlinked files seen so far
+}
+
+impl Loader {
+	// None for excluded items, which ncdu lists but didn't scan
+	fn read_item<R: BufRead>(&mut self, parser: &mut Parser<R>, parent: Option<&str>, parent_device: u64) -> io::Result<Option<Node>> {
+		let is_dir = parser.peek()? == b'[';
+		if is_dir {
+			parser.expect(b'[')?;
+		}
+
+		let info = read_info(parser)?;
+		let path = match parent {
+			Some(parent) => Path::new(parent).join(&info.name),
+			None => Path::new(&info.name).to_owned(),
+		};
+		let device = info.device.unwrap_or(parent_device);
+
+		let mut children = Vec::new();
+		if is_dir {
+			let full_path = path.to_string_lossy().to_string();
+			while parser.next_or_end(b']')? {
+				if let Some(child) = self.read_item(parser, Some(&full_path), device)? {
+					children.push(child);
+				}
+			}
+		}
+
+		if info.excluded {
+			return Ok(None);
+		}
+
+		// the other links to a file are listed with nothing of their own
+		let counted = is_dir || !info.hardlinked || self.links.insert((device, info.inode));
+		let (bytes, disk_bytes) = if counted { (info.bytes, info.disk_bytes) } else { (0, 0) };
+
+		let metadata = Metadata {
+			modified: info.mtime,
+			mode: info.mode.unwrap_or(match (is_dir, info.notreg) {
+				(true, _) => 0o040755,
+				(false, false) => 0o100644,
+				(false, true) => 0o010644, // some special file, the dump doesn't say which
+			}),
+			uid: info.uid,
+			gid: info.gid,
+			inode: info.inode,
+			device,
+			blocks: disk_bytes.div_ceil(512),
+			storage_class: None,
+		};
+
+		let size = match self.size_mode {
+			SizeMode::Apparent => bytes,
+			SizeMode::Disk => disk_bytes,
+		};
+		let entry = Entry::new(&path, is_dir, size, metadata);
+		children.sort_unstable_by_key(|n: &Node| n.entry.bytes());
+
+		Ok(Some(if is_dir { Node::directory(entry, children) } else { Node::file(entry) }))
+	}
+}
+
+#[derive(Default)]
+struct Info {
+	name: String,
+	bytes: u64,
+	disk_bytes: u64,
+	inode: u64,
+	device: Option<u64>,
+	uid: u32,
+	gid: u32,
+	mode: Option<u32>,
+	mtime: i64,
+	hardlinked: bool,
+	notreg: bool,
+	excluded: bool,
+}
+
+fn read_info<R: BufRead>(parser: &mut Parser<R>) -> io::Result<Info> {
+	let mut info = Info::default();
+
+	parser.object(|parser, key| {
+		match key.as_str() {
+			"name" => info.name = parser.string()?,
+			"asize" => info.bytes = parser.number()? as u64,
+			"dsize" => info.disk_bytes = parser.number()? as u64,
+			"ino" => info.inode = parser.number()? as u64,
+			"dev" => info.device = Some(parser.number()? as u64),
+			"uid" => info.uid = parser.number()? as u32,
+			"gid" => info.gid = parser.number()? as u32,
+			"mode" => info.mode = Some(parser.number()? as u32),
+			"mtime" => info.mtime = parser.number()? as i64,
+			"hlnkc" => info.hardlinked = parser.boolean()?,
+			"notreg" => info.notreg = parser.boolean()?,
+			"excluded" => {
+				// a pattern name, or a boolean in older dumps
+				info.excluded = parser.peek()? != b'f';
+				parser.skip_value()?;
+			},
+			_ => parser.skip_value()?,
+		}
+		Ok(())
+	})?;
+
+	Ok(info)
+}
+
+// the same format back, so ncdu -f can open our scans
+pub fn write<N: TreeNode>(root: &N, filter: &Filter, writer: &mut impl Write) -> io::Result<()> {
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+	writeln!(
+		writer,
+		"[{MAJOR_VERSION},{MINOR_VERSION},{{\"progname\":\"fsexpl\",\"progver\":\"{}\",\"timestamp\":{timestamp}}},",
+		env!("CARGO_PKG_VERSION"),
+	)?;
+	write_item(root, filter, 0, None, writer)?;
+	writer.write_all(b"]\n")
+}
+
+fn write_item<N: TreeNode>(node: &N, filter: &Filter, level: usize, parent_device: Option<u64>, writer: &mut impl Write) -> io::Result<()> {
+	let entry = node.entry();
+	let m = entry.metadata();
+
+	if entry.is_dir() {
+		writer.write_all(b"[")?;
+	}
+
+	writer.write_all(b"{\"name\":")?;
+	json::write_string(writer, &if level == 0 { entry.get_full_path() } else { entry.name().to_string() })?;
+
+	// directories only count their own blocks in ncdu, the totals are summed up when loading
+	let bytes = if entry.is_dir() { 0 } else { entry.bytes() };
+	write!(writer, ",\"asize\":{bytes},\"dsize\":{},\"ino\":{}", m.blocks * 512, m.inode)?;
+	if parent_device != Some(m.device) {
+		write!(writer, ",\"dev\":{}", m.device)?;
+	}
+	write!(writer, ",\"uid\":{},\"gid\":{},\"mode\":{},\"mtime\":{}", m.uid, m.gid, m.mode, m.modified)?;
+	if !entry.is_dir() && !m.is_regular() {
+		writer.write_all(b",\"notreg\":true")?;
+	}
+	writer.write_all(b"}")?;
+
+	if entry.is_dir() {
+		if filter.descends(level) {
+			for child in node.children() {
+				if filter.includes(child.entry(), level + 1) {
+					writer.write_all(b",\n")?;
+					write_item(child, filter, level + 1, Some(m.device), writer)?;
+				}
+			}
+		}
+		writer.write_all(b"]")?;
+	}
+
+	Ok(())
+}
+
+fn invalid(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
@@ -0,0 +1,311 @@
+use std::path::{Path, PathBuf};
+
+use crate::{metadata::Metadata, text::{bytes_to_text, time_to_text}};
+
+// what sizes are measured in, for sorting and tile areas
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Weight {
+	Bytes,
+	Count, // recursive entry count, for inode exhaustion and tiny file hoarding
+}
+
+// everything known about a single path, with the aggregates of its subtree
+#[derive(Clone, Debug)]
+pub struct Entry {
+	path_prefix: String, // full path of the parent, ending with a separator
+	name: String,
+	bytes: u64,
+	files: u64, // recursive file count, 1 for a file
+	dirs: u64, // recursive subdirectory count
+	depth: u64, // levels of the deepest descendant below this entry
+	is_dir: bool,
+	metadata: Metadata,
+}
+
+impl Entry {
+	// bytes only count for files and archives, directories sum their children with aggregate()
+	pub fn new(path: &Path, is_dir: bool, bytes: u64, metadata: Metadata) -> Self {
+		let mut path_prefix = path.to_string_lossy().to_string();
+
+		if path_prefix.len() > 1 && path_prefix.ends_with(['/', '\\']) {
+			path_prefix.pop();
+		}
+
+		// a path without separators, like "." or "C:", is all name
+		let name = match path_prefix.rfind(['/', '\\']) {
+			Some(last) => path_prefix.split_off(last + 1),
+			None => std::mem::take(&mut path_prefix),
+		};
+
+		let mut entry = Self {
+			name,
+			path_prefix,
+			bytes,
+			files: if is_dir { 0 } else { 1 },
+			dirs: 0,
+			depth: 0,
+			is_dir,
+			metadata,
+		};
+		if is_dir && !entry.is_archive() {
+			entry.bytes = 0;
+		}
+		entry
+	}
+
+	// an archive listed as a directory, it keeps its length on disk instead of the sum of its extracted members
+	// so the directories above it add up to what the disk holds
+	pub fn is_archive(&self) -> bool {
+		self.is_dir && self.metadata.mode & 0o170000 == 0o100000
+	}
+
+	// recomputes the aggregates of a directory from its children
+	pub fn aggregate<'a>(&mut self, children: impl IntoIterator<Item = &'a Entry>) {
+		self.set_totals(totals(children));
+	}
+
+	fn set_totals(&mut self, (bytes, files, dirs, depth): (u64, u64, u64, u64)) {
+		if self.is_dir {
+			if !self.is_archive() {
+				self.bytes = bytes;
+			}
+			(self.files, self.dirs, self.depth) = (files, dirs, depth);
+		}
+	}
+
+	pub fn get_full_path(&self) -> String {
+		let mut path = self.path_prefix.clone();
+		path.push_str(&self.name);
+		path
+	}
+
+	// true if the path is this entry or lies below it
+	pub fn contains_path(&self, path: &str) -> bool {
+		let full_path = self.get_full_path();
+		match path.strip_prefix(&full_path) {
+			Some(rest) => rest.is_empty() || rest.starts_with(['/', '\\']) || full_path.ends_with(['/', '\\']),
+			None => false,
+		}
+	}
+
+	// multiline summary for tooltips and the details panel
+	pub fn describe(&self) -> String {
+		format!(
+			"{}\n{} ({} allocated)\n{} files, {} directories, {} levels deep\n{} uid {} gid {}\nmodified {}\ninode {} device {}",
+			self.get_full_path(),
+			bytes_to_text(self.bytes),
+			bytes_to_text(self.metadata.blocks * 512),
+			self.files, self.dirs, self.depth,
+			self.metadata.mode_text(), self.metadata.uid, self.metadata.gid,
+			time_to_text(self.metadata.modified),
+			self.metadata.inode, self.metadata.device,
+		)
+	}
+
+	// "directory", "file", "symlink" or "other" for devices, sockets and pipes
+	pub fn kind(&self) -> &'static str {
+		if self.is_dir {
+			return "directory";
+		}
+
+		if self.metadata.is_regular() {
+			return "file";
+		}
+
+		match self.metadata.mode & 0o170000 {
+			0o120000 => "symlink",
+			_ => "other",
+		}
+	}
+
+	// the extension of a file without the dot, empty for directories and names without one
+	pub fn extension(&self) -> &str {
+		match self.name.rfind('.') {
+			Some(i) if !self.is_dir && i > 0 => &self.name[i + 1..],
+			_ => "",
+		}
+	}
+
+	pub fn weight(&self, weight: Weight) -> u64 {
+		match weight {
+			Weight::Bytes => self.bytes,
+			Weight::Count => self.files + self.dirs + self.is_dir as u64,
+		}
+	}
+
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	pub fn path_prefix(&self) -> &str {
+		&self.path_prefix
+	}
+
+	pub fn bytes(&self) -> u64 {
+		self.bytes
+	}
+
+	pub fn files(&self) -> u64 {
+		self.files
+	}
+
+	pub fn dirs(&self) -> u64 {
+		self.dirs
+	}
+
+	pub fn depth(&self) -> u64 {
+		self.depth
+	}
+
+	pub fn is_dir(&self) -> bool {
+		self.is_dir
+	}
+
+	pub fn metadata(&self) -> &Metadata {
+		&self.metadata
+	}
+}
+
+// bytes, files, dirs and depth of a directory with these children
+fn totals<'a>(children: impl IntoIterator<Item = &'a Entry>) -> (u64, u64, u64, u64) {
+	let (mut bytes, mut files, mut dirs, mut depth) = (0, 0, 0, 0);
+
+	for child in children {
+		bytes += child.bytes;
+		files += child.files;
+		dirs += child.dirs + child.is_dir as u64;
+		depth = depth.max(child.depth + 1);
+	}
+
+	(bytes, files, dirs, depth)
+}
+
+// a scanned tree without any presentation state
+#[derive(Clone, Debug)]
+pub struct Node {
+	pub entry: Entry,
+	pub children: Vec<Node>,
+}
+
+impl Node {
+	// a directory with its aggregates computed from the children
+	pub fn directory(mut entry: Entry, children: Vec<Node>) -> Self {
+		entry.aggregate(children.iter().map(|c| &c.entry));
+		Self { entry, children }
+	}
+
+	pub fn file(entry: Entry) -> Self {
+		Self { entry, children: Vec::new() }
+	}
+}
+
+impl TreeNode for Node {
+	fn entry(&self) -> &Entry {
+		&self.entry
+	}
+
+	fn entry_mut(&mut self) -> &mut Entry {
+		&mut self.entry
+	}
+
+	fn children(&self) -> &[Self] {
+		&self.children
+	}
+
+	fn children_mut(&mut self) -> &mut Vec<Self> {
+		&mut self.children
+	}
+}
+
+// the tree operations, for the plain model and for consumers that keep their own state per node
+pub trait TreeNode: Sized {
+	fn entry(&self) -> &Entry;
+	fn entry_mut(&mut self) -> &mut Entry;
+	fn children(&self) -> &[Self];
+	fn children_mut(&mut self) -> &mut Vec<Self>;
+
+	fn find(&self, path: &str) -> Option<&Self> {
+		if !self.entry().contains_path(path) {
+			return None;
+		}
+		if self.entry().get_full_path() == path {
+			return Some(self);
+		}
+
+		self.children().iter().find_map(|child| child.find(path))
+	}
+
+	fn find_mut(&mut self, path: &str) -> Option<&mut Self> {
+		if !self.entry().contains_path(path) {
+			return None;
+		}
+		if self.entry().get_full_path() == path {
+			return Some(self);
+		}
+
+		self.children_mut().iter_mut().find_map(|child| child.find_mut(path))
+	}
+
+	// detaches the descendant with the given path, updating the aggregates of its ancestors
+	fn remove(&mut self, path: &str) -> Option<Self> {
+		if !self.entry().contains_path(path) {
+			return None;
+		}
+
+		let removed = match self.children().iter().position(|child| child.entry().get_full_path() == path) {
+			Some(index) => self.children_mut().remove(index),
+			None => self.children_mut().iter_mut().find_map(|child| child.remove(path))?,
+		};
+
+		self.update_aggregates();
+		Some(removed)
+	}
+
+	// attaches a node under its parent directory (taken from its path prefix), gives it back if the parent isn't in this subtree
+	fn insert(&mut self, mut node: Self) -> Result<(), Box<Self>> {
+		let prefix = node.entry().path_prefix();
+		let parent_path = match prefix.trim_end_matches(['/', '\\']) {
+			"" => prefix,
+			trimmed => trimmed,
+		}.to_string();
+		if !self.entry().is_dir() || !self.entry().contains_path(&parent_path) {
+			return Err(Box::new(node));
+		}
+
+		if self.entry().get_full_path() == parent_path {
+			self.children_mut().push(node);
+		}
+		else {
+			for child in self.children_mut() {
+				node = match child.insert(node) {
+					Err(node) => *node,
+					Ok(()) => {
+						self.update_aggregates();
+						return Ok(());
+					},
+				};
+			}
+			return Err(Box::new(node));
+		}
+
+		self.update_aggregates();
+		Ok(())
+	}
+
+	fn update_aggregates(&mut self) {
+		let totals = totals(self.children().iter().map(|c| c.entry()));
+		self.entry_mut().set_totals(totals);
+	}
+
+	// (path, bytes) of every regular file in the subtree
+	fn collect_files(&self, files: &mut Vec<(PathBuf, u64)>) {
+		if self.entry().is_dir() {
+			for child in self.children() {
+				child.collect_files(files);
+			}
+		}
+		else {
+			files.push((PathBuf::from(self.entry().get_full_path()), self.entry().bytes()));
+		}
+	}
+}
@@ -0,0 +1,445 @@
+use std::{io::{self, Write}, path::{Path, PathBuf}, process::{Command, Stdio}, sync::Mutex, collections::{HashMap, HashSet}, env};
+
+use crate::{metadata::{Metadata, StorageClass}, source::{FileSource, Stat, Contents}, text::text_to_time};
+
+// buckets of s3 and compatible object stores, listed with ListObjectsV2 through curl, which also signs the requests
+//
+// the keys below the scanned prefix are listed in one go, without a delimiter, and split at slashes: the parts before
+// the last slash are the directories and the objects the files, paths in the tree are s3://bucket/key
+// an object with the key of a directory, which a bucket allows beside keys below it, is the file "key (object)"
+//
+// the usual environment variables say where and who:
+//   AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY, with AWS_SESSION_TOKEN for temporary ones, none for public buckets
+//   AWS_REGION or AWS_DEFAULT_REGION, us-east-1 by default
+//   AWS_ENDPOINT_URL for minio and other compatible stores, the bucket always goes into the path
+
+pub fn is_url(path: &Path) -> bool {
+	path.to_str().is_some_and(|p| p.starts_with("s3://"))
+}
+
+// a page of a listing
+struct Listing {
+	objects: Vec<(String, Stat)>, // keys
+	next: Option<String>, // continuation token of the next page
+}
+
+pub struct S3 {
+	bucket: String,
+	endpoint: String, // without a trailing slash
+	region: String,
+	credentials: Option<(String, String, Option<String>)>, // key id, secret, session token
+	listed: Mutex<HashMap<String, Stat>>, // the objects and directories of the walk not stat'ed yet, saving a request per entry
+	children: Mutex<HashMap<String, Vec<String>>>, // the entries of the directories of the walk not listed yet
+}
+
+impl S3 {
+	// the bucket of an s3://bucket/prefix url, the path to scan is the url without a trailing slash
+	pub fn connect(url: &str) -> io::Result<(Self, PathBuf)> {
+		let rest = url.strip_prefix("s3://").ok_or_else(|| invalid_input("not an s3:// url"))?;
+		let (bucket, prefix) = rest.split_once('/').unwrap_or((rest, ""));
+		if bucket.is_empty() || !bucket.bytes().all(|b| b.is_ascii_alphanumeric() || b"-._".contains(&b)) {
+			return Err(invalid_input("no bucket in the s3:// url"));
+		}
+
+		let variable = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+		let region = variable("AWS_REGION").or_else(|| variable("AWS_DEFAULT_REGION")).unwrap_or_else(|| "us-east-1".to_string());
+		let endpoint = variable("AWS_ENDPOINT_URL").unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+		let credentials = variable("AWS_ACCESS_KEY_ID")
+			.zip(variable("AWS_SECRET_ACCESS_KEY"))
+			.map(|(id, secret)| (id, secret, variable("AWS_SESSION_TOKEN")));
+
+		let s3 = Self {
+			bucket: bucket.to_string(),
+			endpoint: endpoint.trim_end_matches('/').to_string(),
+			region,
+			credentials,
+			listed: Mutex::new(HashMap::new()),
+			children: Mutex::new(HashMap::new()),
+		};
+		let root = s3.local(prefix.trim_end_matches('/'));
+		Ok((s3, root))
+	}
+
+	// the key of a path in the tree, empty for the bucket itself
+	fn key<'a>(&self, path: &'a Path) -> io::Result<&'a str> {
+		let bucket = path.to_str()
+			.and_then(|p| p.strip_prefix("s3://"))
+			.and_then(|p| p.strip_prefix(self.bucket.as_str()))
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a path in this bucket"))?;
+		match bucket {
+			"" => Ok(""),
+			key => key.strip_prefix('/').ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a path in this bucket")),
+		}
+	}
+
+	fn local(&self, key: &str) -> PathBuf {
+		if key.is_empty() {
+			PathBuf::from(format!("s3://{}", self.bucket))
+		}
+		else {
+			PathBuf::from(format!("s3://{}/{key}", self.bucket))
+		}
+	}
+
+	// a page of the keys starting with the prefix
+	fn list_objects(&self, prefix: &str, max_keys: Option<u32>, token: Option<&str>) -> io::Result<Listing> {
+		// in the sorted order signing needs
+		let mut query = Vec::new();
+		if let Some(token) = token {
+			query.push(format!("continuation-token={}", encode(token)));
+		}
+		query.push("list-type=2".to_string());
+		if let Some(max_keys) = max_keys {
+			query.push(format!("max-keys={max_keys}"));
+		}
+		query.push(format!("prefix={}", encode(prefix)));
+
+		let xml = self.get(&format!("{}/{}?{}", self.endpoint, self.bucket, query.join("&")))?;
+		Ok(parse_listing(&xml))
+	}
+
+	// every key below the directory, with the entries of each directory below it kept for the list and stat calls that follow
+	fn walk(&self, directory: &str) -> io::Result<()> {
+		self.walk_pages(directory, |prefix, token| self.list_objects(prefix, None, token))
+	}
+
+	// the walk with the pages coming from anywhere, one call per continuation token
+	fn walk_pages(&self, directory: &str, mut page: impl FnMut(&str, Option<&str>) -> io::Result<Listing>) -> io::Result<()> {
+		let prefix = if directory.is_empty() { String::new() } else { format!("{directory}/") };
+
+		let mut objects = Vec::new();
+		let mut token = None;
+		loop {
+			let listing = page(&prefix, token.as_deref())?;
+			objects.extend(listing.objects);
+			token = listing.next;
+			if token.is_none() {
+				break;
+			}
+		}
+
+		// the directories are the parts of the keys before a slash, below the one walked
+		let mut directories = HashSet::new();
+		for (key, _) in &objects {
+			let mut rest = &key[..key.rfind('/').unwrap_or(0)];
+			while rest.len() >= prefix.len() && directories.insert(rest.to_string()) {
+				rest = &rest[..rest.rfind('/').unwrap_or(0)];
+			}
+		}
+		directories.remove(directory);
+
+		let mut listed = self.listed.lock().unwrap();
+		let mut children = self.children.lock().unwrap();
+		// every directory is known to be walked, empty folders included
+		for key in directories.iter().map(String::as_str).chain([directory]) {
+			children.insert(key.to_string(), Vec::new());
+		}
+		let stat = Stat::from_metadata(0, Metadata { mode: 0o040755, ..Default::default() });
+		let entries = directories.iter().map(|d| (d.clone(), stat))
+			// the empty objects consoles create as folders are the directories themselves
+			.chain(objects.into_iter().filter(|(key, _)| !key.ends_with('/')).map(|(key, stat)| {
+				if directories.contains(&key) { (format!("{key} (object)"), stat) } else { (key, stat) }
+			}));
+		for (key, stat) in entries {
+			children.entry(parent(&key).to_string()).or_default().push(key.clone());
+			listed.insert(key, stat);
+		}
+
+		Ok(())
+	}
+
+	// the body of a get request, with the http errors turned into io errors
+	fn get(&self, url: &str) -> io::Result<String> {
+		// the options go through standard input, so the secret doesn't show up in the process list
+		let mut config = format!("url = {}\n", quote(url));
+		if let Some((id, secret, token)) = &self.credentials {
+			config += &format!("aws-sigv4 = {}\n", quote(&format!("aws:amz:{}:s3", self.region)));
+			config += &format!("user = {}\n", quote(&format!("{id}:{secret}")));
+			if let Some(token) = token {
+				config += &format!("header = {}\n", quote(&format!("x-amz-security-token: {token}")));
+			}
+		}
+
+		let mut child = Command::new("curl")
+			.args(["--silent", "--show-error", "--config", "-", "--write-out", "\n%{http_code}"])
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::piped())
+			.spawn()?;
+		child.stdin.take().ok_or_else(|| io::Error::other("no curl input"))?.write_all(config.as_bytes())?;
+		let output = child.wait_with_output()?;
+		if !output.status.success() {
+			return Err(io::Error::other(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+		}
+
+		let body = String::from_utf8_lossy(&output.stdout);
+		let (body, status) = body.rsplit_once('\n').ok_or_else(|| io::Error::other("no http status from curl"))?;
+		let message = || element(body, "Message").map(unescape).unwrap_or_else(|| format!("http status {status}"));
+		match status {
+			"200" => Ok(body.to_string()),
+			"403" => Err(io::Error::new(io::ErrorKind::PermissionDenied, message())),
+			"404" => Err(io::Error::new(io::ErrorKind::NotFound, message())),
+			_ => Err(io::Error::other(message())),
+		}
+	}
+}
+
+impl FileSource for S3 {
+	fn stat(&self, path: &Path, _follow_symlinks: bool) -> io::Result<Stat> {
+		let key = self.key(path)?;
+		if let Some(stat) = self.listed.lock().unwrap().remove(key) {
+			return Ok(stat);
+		}
+
+		let directory = Stat::from_metadata(0, Metadata { mode: 0o040755, ..Default::default() });
+		// the bucket is listed to find out whether it can be
+		if key.is_empty() {
+			return self.list_objects("", Some(1), None).map(|_| directory);
+		}
+
+		// anything below the key makes it a directory, otherwise the key sorts first among the keys it prefixes
+		if self.list_objects(&format!("{key}/"), Some(1), None).is_ok_and(|l| !l.objects.is_empty()) {
+			return Ok(directory);
+		}
+		match self.list_objects(key, Some(1), None)?.objects.into_iter().next() {
+			Some((first, stat)) if first == key => Ok(stat),
+			_ => Err(io::Error::new(io::ErrorKind::NotFound, "no such key")),
+		}
+	}
+
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+		let key = self.key(path)?;
+
+		// the directory the scan starts at is walked, those below it were already
+		if !self.children.lock().unwrap().contains_key(key) {
+			self.walk(key)?;
+		}
+		let children = self.children.lock().unwrap().remove(key).unwrap_or_default();
+		Ok(children.iter().map(|child| Ok(self.local(child))).collect())
+	}
+
+	fn read(&self, _path: &Path) -> io::Result<Box<dyn Contents + '_>> {
+		Err(io::Error::new(io::ErrorKind::Unsupported, "objects are only listed"))
+	}
+}
+
+// the objects and the continuation token of a ListObjectsV2 answer
+fn parse_listing(xml: &str) -> Listing {
+	let mut objects = Vec::new();
+	for object in elements(xml, "Contents") {
+		let key = element(object, "Key").map(unescape).unwrap_or_default();
+		let len: u64 = element(object, "Size").and_then(|s| s.trim().parse().ok()).unwrap_or(0);
+		let metadata = Metadata {
+			modified: element(object, "LastModified").and_then(text_to_time).unwrap_or(0),
+			mode: 0o100644,
+			blocks: len.div_ceil(512),
+			storage_class: Some(element(object, "StorageClass").map_or(StorageClass::Standard, StorageClass::from_name)),
+			..Default::default()
+		};
+		objects.push((key, Stat::from_metadata(len, metadata)));
+	}
+	let next = match element(xml, "IsTruncated") {
+		Some("true") => element(xml, "NextContinuationToken").map(unescape),
+		_ => None,
+	};
+
+	Listing { objects, next }
+}
+
+// the key of the directory an entry is in, empty for the bucket
+fn parent(key: &str) -> &str {
+	key.rsplit_once('/').map_or("", |(parent, _)| parent)
+}
+
+// percent encoding of everything but the unreserved characters, as signing expects it
+fn encode(text: &str) -> String {
+	text.bytes().map(|b| match b {
+		b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+		_ => format!("%{b:02X}"),
+	}).collect()
+}
+
+// a string in a curl config file
+fn quote(text: &str) -> String {
+	format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// the contents of every <tag>...</tag>, the answers never nest an element in one of the same name
+fn elements<'a>(xml: &'a str, tag: &str) -> impl Iterator<Item = &'a str> {
+	let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+	let mut rest = xml;
+	std::iter::from_fn(move || {
+		let start = rest.find(&open)? + open.len();
+		let end = start + rest[start..].find(&close)?;
+		let contents = &rest[start..end];
+		rest = &rest[end + close.len()..];
+		Some(contents)
+	})
+}
+
+fn element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+	elements(xml, tag).next()
+}
+
+// the five named entities and numeric references
+fn unescape(text: &str) -> String {
+	let mut result = String::with_capacity(text.len());
+	let mut rest = text;
+	while let Some(start) = rest.find('&') {
+		result.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let Some(end) = rest.find(';') else {
+			break;
+		};
+		let decoded = match &rest[1..end] {
+			"amp" => Some('&'),
+			"lt" => Some('<'),
+			"gt" => Some('>'),
+			"quot" => Some('"'),
+			"apos" => Some('\''),
+			reference => reference.strip_prefix('#').and_then(|number| match number.strip_prefix('x') {
+				Some(hex) => u32::from_str_radix(hex, 16).ok(),
+				None => number.parse().ok(),
+			}).and_then(char::from_u32),
+		};
+		match decoded {
+			Some(c) => {
+				result.push(c);
+				rest = &rest[end + 1..];
+			},
+			None => {
+				result.push('&');
+				rest = &rest[1..];
+			},
+		}
+	}
+	result.push_str(rest);
+	result
+}
+
+fn invalid_input(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidInput, what)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bucket() -> S3 {
+		S3 {
+			bucket: "b".to_string(),
+			endpoint: "http://127.0.0.1:9000".to_string(),
+			region: "us-east-1".to_string(),
+			credentials: None,
+			listed: Mutex::new(HashMap::new()),
+			children: Mutex::new(HashMap::new()),
+		}
+	}
+
+	// a ListObjectsV2 answer as s3 and minio write it
+	fn answer(keys: &[(&str, u64, &str)], next: Option<&str>) -> String {
+		let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"http://s3.amazonaws.com/doc/2006-03-01/\"><Name>b</Name>");
+		xml += &format!("<IsTruncated>{}</IsTruncated>", next.is_some());
+		if let Some(next) = next {
+			xml += &format!("<NextContinuationToken>{next}</NextContinuationToken>");
+		}
+		for (key, size, class) in keys {
+			xml += &format!("<Contents><Key>{key}</Key><LastModified>2024-03-05T10:20:30.000Z</LastModified><ETag>&quot;0&quot;</ETag><Size>{size}</Size><StorageClass>{class}</StorageClass></Contents>");
+		}
+		xml + "</ListBucketResult>"
+	}
+
+	fn list(s3: &S3, key: &str) -> Vec<String> {
+		let mut names: Vec<String> = s3.list(&s3.local(key)).unwrap().into_iter().map(|p| p.unwrap().to_string_lossy().to_string()).collect();
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn walk_splits_keys_into_directories() {
+		let s3 = bucket();
+		let page = answer(&[("logs/2024/jan/a.log", 3000, "STANDARD"), ("logs/2024/old.gz", 70, "GLACIER"), ("logs/empty/", 0, "STANDARD"), ("top.txt", 5, "STANDARD")], None);
+		s3.walk_pages("", |prefix, _| {
+			assert_eq!(prefix, "");
+			Ok(parse_listing(&page))
+		}).unwrap();
+
+		// the way a scan goes through it, every entry stat'ed and the directories listed
+		let mut entries = Vec::new();
+		let mut rest = list(&s3, "");
+		while let Some(path) = rest.pop() {
+			let stat = s3.stat(Path::new(&path), true).unwrap();
+			if stat.is_dir {
+				rest.extend(list(&s3, s3.key(Path::new(&path)).unwrap()));
+			}
+			entries.push((path, stat.is_dir, stat.len, stat.metadata.storage_class));
+		}
+		entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+		// the folder object made the directory, and is no entry of its own
+		assert_eq!(entries, [
+			("s3://b/logs".to_string(), true, 0, None),
+			("s3://b/logs/2024".to_string(), true, 0, None),
+			("s3://b/logs/2024/jan".to_string(), true, 0, None),
+			("s3://b/logs/2024/jan/a.log".to_string(), false, 3000, Some(StorageClass::Standard)),
+			("s3://b/logs/2024/old.gz".to_string(), false, 70, Some(StorageClass::Glacier)),
+			("s3://b/logs/empty".to_string(), true, 0, None),
+			("s3://b/top.txt".to_string(), false, 5, Some(StorageClass::Standard)),
+		]);
+
+		// and nothing is kept once handed out
+		assert!(s3.listed.lock().unwrap().is_empty());
+		assert!(s3.children.lock().unwrap().is_empty());
+	}
+
+	#[test]
+	fn objects_named_like_directories() {
+		let s3 = bucket();
+		let page = answer(&[("a", 1, "STANDARD"), ("a/b", 2, "STANDARD"), ("a/b/c", 3, "STANDARD")], None);
+		s3.walk_pages("", |_, _| Ok(parse_listing(&page))).unwrap();
+
+		assert_eq!(list(&s3, ""), ["s3://b/a", "s3://b/a (object)"]);
+		assert_eq!(list(&s3, "a"), ["s3://b/a/b", "s3://b/a/b (object)"]);
+		assert_eq!(list(&s3, "a/b"), ["s3://b/a/b/c"]);
+		assert_eq!(s3.stat(Path::new("s3://b/a (object)"), true).unwrap().len, 1);
+		assert!(s3.stat(Path::new("s3://b/a"), true).unwrap().is_dir);
+	}
+
+	#[test]
+	fn walk_follows_continuation_tokens() {
+		let s3 = bucket();
+		let pages = [
+			answer(&[("p/x/1", 1, "STANDARD"), ("p/x/2", 2, "STANDARD")], Some("1/2+token=")),
+			answer(&[("p/x/3", 3, "STANDARD")], Some("second")),
+			answer(&[("p/y", 4, "STANDARD")], None),
+		];
+		let mut tokens = Vec::new();
+		s3.walk_pages("p", |prefix, token| {
+			assert_eq!(prefix, "p/");
+			tokens.push(token.map(str::to_string));
+			Ok(parse_listing(&pages[tokens.len() - 1]))
+		}).unwrap();
+
+		assert_eq!(tokens, [None, Some("1/2+token=".to_string()), Some("second".to_string())]);
+		assert_eq!(list(&s3, "p"), ["s3://b/p/x", "s3://b/p/y"]);
+		assert_eq!(list(&s3, "p/x"), ["s3://b/p/x/1", "s3://b/p/x/2", "s3://b/p/x/3"]);
+	}
+
+	#[test]
+	fn xml_elements() {
+		let xml = "<A><Key>one</Key></A><B/><A><Key></Key></A><A><Key>three";
+		assert_eq!(elements(xml, "A").collect::<Vec<_>>(), ["<Key>one</Key>", "<Key></Key>"]);
+		assert_eq!(element(xml, "Key"), Some("one"));
+		assert_eq!(element(xml, "B"), None);
+		assert_eq!(element("<Key>a</Ke", "Key"), None);
+	}
+
+	#[test]
+	fn xml_unescape() {
+		assert_eq!(unescape("a &amp; b &lt;c&gt; &quot;d&quot; &apos;e&apos;"), "a & b <c> \"d\" 'e'");
+		assert_eq!(unescape("&#233;t&#xE9; &#x1F600;"), "été 😀");
+		// anything that isn't a reference stays as it is
+		assert_eq!(unescape("AT&T &bogus; &#xZZ; & end &"), "AT&T &bogus; &#xZZ; & end &");
+		assert_eq!(unescape("&amp;amp;"), "&amp;");
+	}
+}
@@ -0,0 +1,235 @@
+use std::{thread, sync::mpsc::{channel, Receiver, Sender}, path::{Path, PathBuf}, io};
+
+use crate::{node::{Node, Entry}, snapshot, ncdu, du, archive, sftp::{self, Sftp}, s3::{self, S3}, source::{FileSource, Local, Stat}};
+
+#[derive(Debug)]
+pub enum Message {
+	Denied(PathBuf),
+	Progress(u64, u64), // total bytes, total files
+	Finished(Option<Node>), // Some if successful, None if invalid
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SizeMode {
+	#[default]
+	Apparent, // file lengths
+	Disk, // allocated blocks
+}
+
+// settings shared by every scan
+#[derive(Clone, Debug)]
+pub struct ScanOptions {
+	pub excludes: Vec<PathBuf>, // full paths, or bare names matched anywhere
+	pub one_filesystem: bool,
+	pub follow_symlinks: bool, // on by default, the scanner always followed them
+	pub size_mode: SizeMode,
+	pub du: du::Import, // how to read du listings opened instead of a directory
+	pub archives: bool, // list the contents of archives as if they were directories
+}
+
+impl Default for ScanOptions {
+	fn default() -> Self {
+		Self {
+			excludes: Vec::new(),
+			one_filesystem: false,
+			follow_symlinks: true,
+			size_mode: SizeMode::default(),
+			du: du::Import::default(),
+			archives: false,
+		}
+	}
+}
+
+impl ScanOptions {
+	pub fn is_excluded(&self, path: &Path) -> bool {
+		self.excludes.iter().any(|e| {
+			e == path || (e.components().count() == 1 && path.file_name() == Some(e.as_os_str()))
+		})
+	}
+
+	pub fn size(&self, stat: &Stat) -> u64 {
+		match self.size_mode {
+			SizeMode::Apparent => stat.len,
+			SizeMode::Disk => stat.metadata.blocks * 512,
+		}
+	}
+}
+
+// scans the local disk, a remote host or a bucket in a background thread, saved scans are loaded instead
+pub fn spawn(path: &Path, options: &ScanOptions) -> Receiver<Message> {
+	let (sender, receiver) = channel::<Message>();
+
+	let path_buf = path.to_owned();
+	let options = options.clone();
+	thread::spawn(move || {
+		let node = if sftp::is_url(&path_buf) {
+			scan_remote(Sftp::connect(&path_buf.to_string_lossy()), &path_buf, &options, &sender)
+		}
+		else if s3::is_url(&path_buf) {
+			scan_remote(S3::connect(&path_buf.to_string_lossy()), &path_buf, &options, &sender)
+		}
+		else {
+			match load_saved(&path_buf, &options) {
+				Some(Ok(node)) => Some(node),
+				Some(Err(_)) => {
+					sender.send(Message::Denied(path_buf.clone())).unwrap();
+					None
+				},
+				None => scan_from(&Local, &path_buf, &options, &sender),
+			}
+		};
+
+		sender.send(Message::Finished(node)).unwrap();
+	});
+
+	receiver
+}
+
+// scans any source in a background thread
+pub fn spawn_source<S: FileSource + Send + 'static>(source: S, path: &Path, options: &ScanOptions) -> Receiver<Message> {
+	let (sender, receiver) = channel::<Message>();
+
+	let path_buf = path.to_owned();
+	let options = options.clone();
+	thread::spawn(move || {
+		let node = scan_from(&source, &path_buf, &options, &sender);
+		sender.send(Message::Finished(node)).unwrap();
+	});
+
+	receiver
+}
+
+// a source that had to be connected to first, the url is denied when that fails
+fn scan_remote<S: FileSource>(connected: io::Result<(S, PathBuf)>, url: &Path, options: &ScanOptions, sender: &Sender<Message>) -> Option<Node> {
+	match connected {
+		Ok((source, root)) => scan_from(&source, &root, options, sender),
+		Err(_) => {
+			sender.send(Message::Denied(url.to_owned())).unwrap();
+			None
+		},
+	}
+}
+
+// the whole scan with its running totals starting at zero
+pub fn scan_from<S: FileSource>(source: &S, path: &Path, options: &ScanOptions, sender: &Sender<Message>) -> Option<Node> {
+	scan(source, path, options, sender, &mut Walk::default(), None)
+}
+
+// what a scan keeps track of while descending
+#[derive(Default)]
+struct Walk {
+	bytes: u64, // running totals
+	files: u64,
+	directories: Vec<(u64, u64)>, // device and inode of the directories above, to notice symlinks pointing back up
+}
+
+// snapshots, ncdu dumps and du listings, None if the path is none of them
+pub fn load_saved(path: &Path, options: &ScanOptions) -> Option<io::Result<Node>> {
+	if snapshot::is_snapshot(path) {
+		Some(snapshot::load(path))
+	}
+	else if ncdu::is_ncdu(path) {
+		Some(ncdu::load(path, options.size_mode))
+	}
+	else if du::is_du(path) {
+		Some(du::load(path, options.du))
+	}
+	else {
+		None
+	}
+}
+
+// true if the path is scanned from the local disk, not from a remote host, a bucket or a saved scan
+pub fn is_local(path: &Path) -> bool {
+	!sftp::is_url(path) && !s3::is_url(path) && !snapshot::is_snapshot(path) && !ncdu::is_ncdu(path) && !du::is_du(path)
+}
+
+// scans and waits for the result, for use without a window
+pub fn scan_blocking(path: &Path, options: &ScanOptions, denied: &mut Vec<PathBuf>) -> Option<Node> {
+	wait(spawn(path, options), denied)
+}
+
+// the result of a spawned scan, collecting the paths that couldn't be read
+pub fn wait(receiver: Receiver<Message>, denied: &mut Vec<PathBuf>) -> Option<Node> {
+	for m in receiver {
+		match m {
+			Message::Progress(_, _) => {},
+			Message::Denied(path) => denied.push(path),
+			Message::Finished(node) => return node,
+		}
+	}
+
+	None
+}
+
+// walks the path recursively, reporting unreadable paths and the running totals through the sender
+fn scan<S: FileSource>(source: &S, path: &Path, options: &ScanOptions, sender: &Sender<Message>, walk: &mut Walk, parent_device: Option<u64>) -> Option<Node> {
+	if options.is_excluded(path) {
+		return None;
+	}
+
+	let stat = match source.stat(path, options.follow_symlinks) {
+		Ok(m) => m,
+		Err(_) => {
+			sender.send(Message::Denied(path.to_owned())).unwrap();
+			return None;
+		}
+	};
+
+	// mount points stay out of the scan, the same as du -x
+	if options.one_filesystem && parent_device.is_some_and(|device| device != stat.metadata.device) {
+		return None;
+	}
+
+	if !stat.is_dir {
+		let bytes = options.size(&stat);
+		walk.bytes += bytes;
+		walk.files += 1;
+		if walk.files.is_multiple_of(100) {
+			sender.send(Message::Progress(walk.bytes, walk.files)).unwrap();
+		}
+
+		// archives that can't be read stay plain files
+		let kind = archive::Kind::from_path(path).filter(|_| options.archives && stat.is_file);
+		if let Some(members) = kind.and_then(|kind| archive::list(source.read(path).ok()?, kind).ok()) {
+			return Some(archive::tree(path, bytes, stat.metadata, members, options.size_mode));
+		}
+
+		return Some(Node::file(Entry::new(path, false, bytes, stat.metadata)));
+	}
+
+	// a followed symlink to a directory above would be walked forever, sources without inodes can't tell
+	let id = (stat.metadata.device, stat.metadata.inode);
+	if options.follow_symlinks && id.1 != 0 && walk.directories.contains(&id) {
+		return None;
+	}
+
+	let iterator = match source.list(path) {
+		Ok(i) => i,
+		Err(_) => {
+			sender.send(Message::Denied(path.to_owned())).unwrap();
+			return None;
+		},
+	};
+
+	walk.directories.push(id);
+	let mut children = Vec::new();
+	for entry in iterator {
+		let entry = match entry {
+			Ok(e) => e,
+			Err(_) => {
+				sender.send(Message::Denied(path.to_owned())).unwrap();
+				continue;
+			}
+		};
+
+		if let Some(child) = scan(source, &entry, options, sender, walk, Some(stat.metadata.device)) {
+			children.push(child);
+		}
+	}
+	walk.directories.pop();
+
+	children.sort_unstable_by_key(|n| n.entry.bytes());
+
+	Some(Node::directory(Entry::new(path, true, options.size(&stat), stat.metadata), children))
+}
@@ -0,0 +1,430 @@
+use std::{io::{self, Read, Write, Seek, SeekFrom, BufReader, BufWriter}, path::{Path, PathBuf}, process::{Command, Child, ChildStdin, ChildStdout, Stdio}, sync::Mutex, collections::{HashMap, hash_map::DefaultHasher}, hash::{Hash, Hasher}};
+
+use crate::{metadata::Metadata, source::{FileSource, Stat, Contents}};
+
+// remote hosts scanned over sftp version 3, spoken to the sftp subsystem of the ssh client, so nothing has to be
+// installed on the host and ssh handles the keys, the agent, known hosts and its config
+//
+// packets: u32 length, u8 type, u32 request id, then the fields of the type
+// strings are a u32 length followed by the bytes, attributes a u32 of flags followed by the fields they announce
+//
+// paths in the tree keep the sftp://[user@]host[:port] prefix, so they are never mistaken for local paths
+
+const INIT: u8 = 1;
+const VERSION: u8 = 2;
+const OPEN: u8 = 3;
+const CLOSE: u8 = 4;
+const READ: u8 = 5;
+const LSTAT: u8 = 7;
+const OPENDIR: u8 = 11;
+const READDIR: u8 = 12;
+const REALPATH: u8 = 16;
+const STAT: u8 = 17;
+const STATUS: u8 = 101;
+const HANDLE: u8 = 102;
+const DATA: u8 = 103;
+const NAME: u8 = 104;
+const ATTRS: u8 = 105;
+
+const STATUS_EOF: u32 = 1;
+const STATUS_NO_SUCH_FILE: u32 = 2;
+const STATUS_PERMISSION_DENIED: u32 = 3;
+
+const ATTR_SIZE: u32 = 0x1;
+const ATTR_UIDGID: u32 = 0x2;
+const ATTR_PERMISSIONS: u32 = 0x4;
+const ATTR_ACMODTIME: u32 = 0x8;
+const ATTR_EXTENDED: u32 = 0x80000000;
+
+const OPEN_READ: u32 = 0x1;
+const MAX_READ: u32 = 32 * 1024; // what every server answers in full
+const MAX_PACKET: usize = 256 * 1024;
+
+pub fn is_url(path: &Path) -> bool {
+	path.to_str().is_some_and(|p| p.starts_with("sftp://"))
+}
+
+// the parts of sftp://[user@]host[:port][/path]
+struct Url<'a> {
+	prefix: &'a str,
+	user: Option<&'a str>,
+	host: &'a str,
+	port: Option<&'a str>,
+	path: &'a str,
+}
+
+impl<'a> Url<'a> {
+	fn parse(url: &'a str) -> io::Result<Self> {
+		let rest = url.strip_prefix("sftp://").ok_or_else(|| invalid_input("not an sftp:// url"))?;
+		let authority_end = rest.find('/').unwrap_or(rest.len());
+		let (authority, path) = rest.split_at(authority_end);
+
+		let (user, host_port) = match authority.rsplit_once('@') {
+			Some((user, host_port)) => (Some(user), host_port),
+			None => (None, authority),
+		};
+		// the port is whatever follows the last colon, unless that is inside the brackets of an ipv6 address
+		let (host, port) = match host_port.rsplit_once(':') {
+			Some((host, port)) if !port.contains(']') && port.bytes().all(|b| b.is_ascii_digit()) => (host, Some(port)),
+			_ => (host_port, None),
+		};
+		let host = host.trim_start_matches('[').trim_end_matches(']');
+
+		// anything starting with a dash would be taken as an option by ssh
+		if host.is_empty() || host.starts_with('-') || user.is_some_and(|u| u.is_empty() || u.starts_with('-')) {
+			return Err(invalid_input("no host in the sftp:// url"));
+		}
+
+		Ok(Self { prefix: &url[..url.len() - path.len()], user, host, port, path })
+	}
+}
+
+// a request and the answer to it, one at a time
+struct Connection {
+	child: Child,
+	input: BufWriter<ChildStdin>,
+	output: BufReader<ChildStdout>,
+	next_id: u32,
+}
+
+impl Connection {
+	fn request(&mut self, kind: u8, fields: &[u8]) -> io::Result<(u8, Vec<u8>)> {
+		let id = self.next_id;
+		self.next_id = self.next_id.wrapping_add(1);
+
+		self.input.write_all(&(fields.len() as u32 + 5).to_be_bytes())?;
+		self.input.write_all(&[kind])?;
+		self.input.write_all(&id.to_be_bytes())?;
+		self.input.write_all(fields)?;
+		self.input.flush()?;
+
+		let (kind, packet) = self.receive()?;
+		let mut reader = Fields::new(&packet);
+		if reader.u32()? != id {
+			return Err(invalid_data("sftp answer to another request"));
+		}
+		Ok((kind, packet[4..].to_vec()))
+	}
+
+	fn receive(&mut self) -> io::Result<(u8, Vec<u8>)> {
+		let mut header = [0; 5];
+		self.output.read_exact(&mut header)?;
+		let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+		if length == 0 || length > MAX_PACKET {
+			return Err(invalid_data("bad sftp packet length"));
+		}
+
+		let mut packet = vec![0; length - 1];
+		self.output.read_exact(&mut packet)?;
+		Ok((header[4], packet))
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+	}
+}
+
+pub struct Sftp {
+	prefix: String, // sftp://[user@]host[:port], put in front of the remote paths
+	connection: Mutex<Connection>,
+	listed: Mutex<HashMap<String, Stat>>, // the attributes readdir sent along, saving a stat per entry
+}
+
+impl Sftp {
+	// connects with the ssh client, the path to scan is the one in the url made absolute
+	pub fn connect(url: &str) -> io::Result<(Self, PathBuf)> {
+		let url = Url::parse(url)?;
+
+		let mut command = Command::new("ssh");
+		// never stop for a password prompt, keys come from the agent or the ssh config
+		command.args(["-o", "BatchMode=yes"]);
+		if let Some(port) = url.port {
+			command.args(["-p", port]);
+		}
+		if let Some(user) = url.user {
+			command.args(["-l", user]);
+		}
+		command.args(["-s", url.host, "sftp"]);
+
+		let sftp = Self::spawn(command, url.prefix)?;
+		let root = sftp.local(&sftp.realpath(if url.path.is_empty() { "." } else { url.path })?);
+		Ok((sftp, root))
+	}
+
+	// any command speaking sftp on its standard input and output, like ssh -s or sftp-server itself
+	pub fn spawn(mut command: Command, prefix: &str) -> io::Result<Self> {
+		let mut child = command.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn()?;
+		let input = BufWriter::new(child.stdin.take().ok_or_else(|| io::Error::other("no sftp input"))?);
+		let output = BufReader::new(child.stdout.take().ok_or_else(|| io::Error::other("no sftp output"))?);
+		let mut connection = Connection { child, input, output, next_id: 0 };
+
+		// the version exchange is the only packet without a request id
+		connection.input.write_all(&5u32.to_be_bytes())?;
+		connection.input.write_all(&[INIT])?;
+		connection.input.write_all(&3u32.to_be_bytes())?;
+		connection.input.flush()?;
+		let (kind, packet) = connection.receive()?;
+		if kind != VERSION || Fields::new(&packet).u32()? < 3 {
+			return Err(invalid_data("the server doesn't speak sftp version 3"));
+		}
+
+		Ok(Self { prefix: prefix.to_string(), connection: Mutex::new(connection), listed: Mutex::new(HashMap::new()) })
+	}
+
+	fn request(&self, kind: u8, fields: &[u8]) -> io::Result<(u8, Vec<u8>)> {
+		self.connection.lock().unwrap().request(kind, fields)
+	}
+
+	// the remote path of a path in the tree
+	fn remote<'a>(&self, path: &'a Path) -> io::Result<&'a str> {
+		path.to_str()
+			.and_then(|p| p.strip_prefix(&self.prefix))
+			.filter(|p| p.starts_with('/'))
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a path on this host"))
+	}
+
+	fn local(&self, remote: &str) -> PathBuf {
+		PathBuf::from(format!("{}{remote}", self.prefix))
+	}
+
+	fn realpath(&self, path: &str) -> io::Result<String> {
+		match self.request(REALPATH, &string(path.as_bytes()))? {
+			(NAME, packet) => {
+				let mut reader = Fields::new(&packet);
+				if reader.u32()? == 0 {
+					return Err(invalid_data("empty sftp realpath answer"));
+				}
+				Ok(String::from_utf8_lossy(reader.string()?).to_string())
+			},
+			(kind, packet) => Err(status(kind, &packet)),
+		}
+	}
+
+	fn handle(&self, kind: u8, fields: &[u8]) -> io::Result<Vec<u8>> {
+		match self.request(kind, fields)? {
+			(HANDLE, packet) => Ok(Fields::new(&packet).string()?.to_vec()),
+			(kind, packet) => Err(status(kind, &packet)),
+		}
+	}
+
+	fn close(&self, handle: &[u8]) {
+		let _ = self.request(CLOSE, &string(handle));
+	}
+
+	// the entries of a readdir answer, each with its attributes kept for the stat that follows
+	fn names(&self, directory: &str, packet: &[u8], entries: &mut Vec<io::Result<PathBuf>>) -> io::Result<()> {
+		let mut reader = Fields::new(packet);
+		let mut listed = self.listed.lock().unwrap();
+		for _ in 0..reader.u32()? {
+			let name = String::from_utf8_lossy(reader.string()?).to_string();
+			let _long_name = reader.string()?;
+			let stat = attributes(&mut reader)?;
+			if name == "." || name == ".." {
+				continue;
+			}
+
+			let child = format!("{}/{name}", directory.trim_end_matches('/'));
+			entries.push(Ok(self.local(&child)));
+			listed.insert(child, stat);
+		}
+		Ok(())
+	}
+}
+
+impl FileSource for Sftp {
+	fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<Stat> {
+		let remote = self.remote(path)?;
+
+		// each listed entry is stat'ed once, so its attributes are dropped as they are used
+		// symlinks have to be asked for again when following them
+		let listed = self.listed.lock().unwrap().remove(remote);
+		let mut stat = match listed {
+			Some(stat) if !follow_symlinks || stat.metadata.mode & 0o170000 != 0o120000 => stat,
+			_ => match self.request(if follow_symlinks { STAT } else { LSTAT }, &string(remote.as_bytes()))? {
+				(ATTRS, packet) => attributes(&mut Fields::new(&packet))?,
+				(kind, packet) => return Err(status(kind, &packet)),
+			},
+		};
+
+		// sftp has no inodes, directories get one made from their real path so the scan notices symlinks pointing back up
+		if follow_symlinks && stat.is_dir {
+			let mut hasher = DefaultHasher::new();
+			self.realpath(remote)?.hash(&mut hasher);
+			stat.metadata.inode = hasher.finish().max(1);
+		}
+		Ok(stat)
+	}
+
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+		let remote = self.remote(path)?;
+		let handle = self.handle(OPENDIR, &string(remote.as_bytes()))?;
+
+		let mut entries = Vec::new();
+		let result = loop {
+			let (kind, packet) = match self.request(READDIR, &string(&handle)) {
+				Ok(answer) => answer,
+				Err(e) => break Err(e),
+			};
+			if kind != NAME {
+				break match status(kind, &packet) {
+					e if e.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+					e => Err(e),
+				};
+			}
+			if let Err(e) = self.names(remote, &packet, &mut entries) {
+				break Err(e);
+			}
+		};
+
+		self.close(&handle);
+		result.map(|_| entries)
+	}
+
+	fn read(&self, path: &Path) -> io::Result<Box<dyn Contents + '_>> {
+		let len = self.stat(path, true)?.len;
+		let mut fields = string(self.remote(path)?.as_bytes());
+		fields.extend_from_slice(&OPEN_READ.to_be_bytes());
+		fields.extend_from_slice(&0u32.to_be_bytes()); // no attributes
+		let handle = self.handle(OPEN, &fields)?;
+		Ok(Box::new(RemoteFile { sftp: self, handle, offset: 0, len }))
+	}
+}
+
+// a file opened for reading, closed when dropped
+struct RemoteFile<'a> {
+	sftp: &'a Sftp,
+	handle: Vec<u8>,
+	offset: u64,
+	len: u64,
+}
+
+impl Read for RemoteFile<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if buf.is_empty() || self.offset >= self.len {
+			return Ok(0);
+		}
+
+		let mut fields = string(&self.handle);
+		fields.extend_from_slice(&self.offset.to_be_bytes());
+		fields.extend_from_slice(&(buf.len() as u32).min(MAX_READ).to_be_bytes());
+		match self.sftp.request(READ, &fields)? {
+			(DATA, packet) => {
+				let data = Fields::new(&packet).string()?;
+				let n = data.len().min(buf.len());
+				buf[..n].copy_from_slice(&data[..n]);
+				self.offset += n as u64;
+				Ok(n)
+			},
+			(kind, packet) => match status(kind, &packet) {
+				e if e.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+				e => Err(e),
+			},
+		}
+	}
+}
+
+impl Seek for RemoteFile<'_> {
+	fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+		let offset = match position {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+			SeekFrom::Current(delta) => self.offset.checked_add_signed(delta),
+		};
+		self.offset = offset.ok_or_else(|| invalid_input("seek before the start of the file"))?;
+		Ok(self.offset)
+	}
+}
+
+impl Drop for RemoteFile<'_> {
+	fn drop(&mut self) {
+		self.sftp.close(&self.handle);
+	}
+}
+
+// the attributes of version 3: no inode, device or allocated blocks, so -x can't tell mount points apart
+// and the size on disk is the length rounded up to whole blocks
+fn attributes(reader: &mut Fields) -> io::Result<Stat> {
+	let flags = reader.u32()?;
+	let len = if flags & ATTR_SIZE != 0 { reader.u64()? } else { 0 };
+	let (uid, gid) = if flags & ATTR_UIDGID != 0 { (reader.u32()?, reader.u32()?) } else { (0, 0) };
+	let mode = if flags & ATTR_PERMISSIONS != 0 { reader.u32()? } else { 0 };
+	let (_accessed, modified) = if flags & ATTR_ACMODTIME != 0 { (reader.u32()? as i64, reader.u32()? as i64) } else { (0, 0) };
+	if flags & ATTR_EXTENDED != 0 {
+		for _ in 0..reader.u32()? {
+			reader.string()?;
+			reader.string()?;
+		}
+	}
+
+	let metadata = Metadata { modified, mode, uid, gid, inode: 0, device: 0, blocks: len.div_ceil(512), storage_class: None };
+	Ok(Stat::from_metadata(len, metadata))
+}
+
+// the error in a status answer, end of file included
+fn status(kind: u8, packet: &[u8]) -> io::Error {
+	if kind != STATUS {
+		return invalid_data("unexpected sftp answer");
+	}
+
+	let mut reader = Fields::new(packet);
+	let Ok(code) = reader.u32() else {
+		return invalid_data("truncated sftp status");
+	};
+	let message = reader.string().map(|m| String::from_utf8_lossy(m).to_string()).unwrap_or_default();
+	let kind = match code {
+		STATUS_EOF => io::ErrorKind::UnexpectedEof,
+		STATUS_NO_SUCH_FILE => io::ErrorKind::NotFound,
+		STATUS_PERMISSION_DENIED => io::ErrorKind::PermissionDenied,
+		_ => io::ErrorKind::Other,
+	};
+	io::Error::new(kind, message)
+}
+
+fn string(bytes: &[u8]) -> Vec<u8> {
+	let mut field = (bytes.len() as u32).to_be_bytes().to_vec();
+	field.extend_from_slice(bytes);
+	field
+}
+
+// big endian fields of a packet
+struct Fields<'a> {
+	bytes: &'a [u8],
+	at: usize,
+}
+
+impl<'a> Fields<'a> {
+	fn new(bytes: &'a [u8]) -> Self {
+		Self { bytes, at: 0 }
+	}
+
+	fn take(&mut self, n: usize) -> io::Result<&'a [u8]> {
+		let taken = self.bytes.get(self.at..self.at.saturating_add(n)).ok_or_else(|| invalid_data("truncated sftp packet"))?;
+		self.at += n;
+		Ok(taken)
+	}
+
+	fn u32(&mut self) -> io::Result<u32> {
+		let b = self.take(4)?;
+		Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+	}
+
+	fn u64(&mut self) -> io::Result<u64> {
+		Ok((self.u32()? as u64) << 32 | self.u32()? as u64)
+	}
+
+	fn string(&mut self) -> io::Result<&'a [u8]> {
+		let length = self.u32()? as usize;
+		self.take(length)
+	}
+}
+
+fn invalid_data(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+fn invalid_input(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidInput, what)
+}
@@ -0,0 +1,149 @@
+use std::{fs::File, io::{self, Read, Write, BufReader, BufWriter}, path::{Path, PathBuf}};
+
+use crate::{node::{Node, Entry, TreeNode}, metadata::{Metadata, StorageClass}};
+
+// a scanned tree saved to disk, to be opened later or on another machine
+//
+// layout, all integers little endian:
+//   magic, u32 version, string root path
+//   node: string name, u8 is_dir, u64 bytes, metadata, then for directories u32 child count and the children
+//   metadata: i64 modified, u32 mode, uid and gid, u64 inode, device and blocks, u8 storage class (0 for none)
+// strings are a u32 length followed by utf-8, directory aggregates are recomputed when loading
+
+const MAGIC: &[u8; 16] = b"FSEXPL-SNAPSHOT\n";
+const VERSION: u32 = 3; // 1 also stored the access and change times, 1 and 2 had no storage class
+const MAX_STRING: usize = 64 * 1024; // longer than any path, guards against allocating garbage lengths
+
+pub fn save<N: TreeNode>(root: &N, path: &Path) -> io::Result<()> {
+	let mut writer = BufWriter::new(File::create(path)?);
+	write(root, &mut writer)?;
+	writer.flush()
+}
+
+pub fn load(path: &Path) -> io::Result<Node> {
+	read(&mut BufReader::new(File::open(path)?))
+}
+
+pub fn is_snapshot(path: &Path) -> bool {
+	let mut magic = [0; MAGIC.len()];
+	match File::open(path) {
+		Ok(mut file) => path.is_file() && file.read_exact(&mut magic).is_ok() && &magic == MAGIC,
+		Err(_) => false,
+	}
+}
+
+pub fn write<N: TreeNode>(root: &N, writer: &mut impl Write) -> io::Result<()> {
+	writer.write_all(MAGIC)?;
+	writer.write_all(&VERSION.to_le_bytes())?;
+	write_string(writer, &root.entry().get_full_path())?;
+	write_node(root, writer)
+}
+
+pub fn read(reader: &mut impl Read) -> io::Result<Node> {
+	let mut magic = [0; MAGIC.len()];
+	reader.read_exact(&mut magic)?;
+	if &magic != MAGIC {
+		return Err(invalid("not a snapshot"));
+	}
+	let version = read_u32(reader)?;
+	if !(1..=VERSION).contains(&version) {
+		return Err(invalid("unsupported snapshot version"));
+	}
+
+	let path = PathBuf::from(read_string(reader)?);
+	let _name = read_string(reader)?;
+	read_node(reader, &path, version)
+}
+
+fn write_node<N: TreeNode>(node: &N, writer: &mut impl Write) -> io::Result<()> {
+	let entry = node.entry();
+	let m = entry.metadata();
+
+	write_string(writer, entry.name())?;
+	writer.write_all(&[entry.is_dir() as u8])?;
+	writer.write_all(&entry.bytes().to_le_bytes())?;
+	writer.write_all(&m.modified.to_le_bytes())?;
+	for value in [m.mode, m.uid, m.gid] {
+		writer.write_all(&value.to_le_bytes())?;
+	}
+	for value in [m.inode, m.device, m.blocks] {
+		writer.write_all(&value.to_le_bytes())?;
+	}
+	writer.write_all(&[m.storage_class.map_or(0, |class| class.number())])?;
+
+	if entry.is_dir() {
+		writer.write_all(&(node.children().len() as u32).to_le_bytes())?;
+		for child in node.children() {
+			write_node(child, writer)?;
+		}
+	}
+
+	Ok(())
+}
+
+// reads the rest of a node after its name, which the caller already joined into the path
+fn read_node(reader: &mut impl Read, path: &Path, version: u32) -> io::Result<Node> {
+	let is_dir = read_bytes::<1>(reader)?[0] != 0;
+	let bytes = read_u64(reader)?;
+	let modified = read_u64(reader)? as i64;
+	if version == 1 {
+		read_bytes::<16>(reader)?; // the access and change times
+	}
+	let metadata = Metadata {
+		modified,
+		mode: read_u32(reader)?,
+		uid: read_u32(reader)?,
+		gid: read_u32(reader)?,
+		inode: read_u64(reader)?,
+		device: read_u64(reader)?,
+		blocks: read_u64(reader)?,
+		storage_class: if version >= 3 { StorageClass::from_number(read_bytes::<1>(reader)?[0]) } else { None },
+	};
+
+	let entry = Entry::new(path, is_dir, bytes, metadata);
+	if !is_dir {
+		return Ok(Node::file(entry));
+	}
+
+	let count = read_u32(reader)?;
+	let mut children = Vec::new();
+	for _ in 0..count {
+		let name = read_string(reader)?;
+		children.push(read_node(reader, &path.join(&name), version)?);
+	}
+
+	Ok(Node::directory(entry, children))
+}
+
+fn write_string(writer: &mut impl Write, text: &str) -> io::Result<()> {
+	writer.write_all(&(text.len() as u32).to_le_bytes())?;
+	writer.write_all(text.as_bytes())
+}
+
+fn read_string(reader: &mut impl Read) -> io::Result<String> {
+	let length = read_u32(reader)? as usize;
+	if length > MAX_STRING {
+		return Err(invalid("string too long"));
+	}
+	let mut bytes = vec![0; length];
+	reader.read_exact(&mut bytes)?;
+	String::from_utf8(bytes).map_err(|_| invalid("name is not utf-8"))
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> io::Result<[u8; N]> {
+	let mut bytes = [0; N];
+	reader.read_exact(&mut bytes)?;
+	Ok(bytes)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+	Ok(u32::from_le_bytes(read_bytes(reader)?))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+	Ok(u64::from_le_bytes(read_bytes(reader)?))
+}
+
+fn invalid(what: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, what)
+}
@@ -0,0 +1,187 @@
+use std::{fs::{self, File}, io::{self, Read, Seek, Cursor}, path::{Path, PathBuf}, collections::{BTreeMap, BTreeSet}};
+
+use crate::{node::{Node, TreeNode}, metadata::Metadata, archive, scan::{self, ScanOptions, SizeMode}};
+
+// where a scan reads its entries from: the local disk, or anything else shaped like a tree of files
+//
+// paths are full paths as the source names them, the entries of a directory are its path joined with their names
+
+// what a scan needs to know about a single path
+#[derive(Clone, Copy, Debug)]
+pub struct Stat {
+	pub is_dir: bool,
+	pub is_file: bool, // regular files, the only ones that are looked into as archives
+	pub len: u64,
+	pub metadata: Metadata,
+}
+
+impl Stat {
+	// the type taken from the mode bits, like the sources without a separate file type report it
+	pub fn from_metadata(len: u64, metadata: Metadata) -> Self {
+		let kind = metadata.mode & 0o170000;
+		Self { is_dir: kind == 0o040000, is_file: kind == 0o100000, len, metadata }
+	}
+
+	fn from_fs(metadata: &fs::Metadata) -> Self {
+		Self { is_dir: metadata.is_dir(), is_file: metadata.is_file(), len: metadata.len(), metadata: Metadata::from_fs(metadata) }
+	}
+}
+
+// the contents of a file, seekable so archives can be listed from their end
+pub trait Contents: Read + Seek {}
+
+impl<T: Read + Seek> Contents for T {}
+
+pub trait FileSource {
+	// the path itself, or what it links to when following symlinks
+	fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<Stat>;
+
+	// full paths of the entries in a directory, entries that can't be read are errors of their own
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>>;
+
+	fn read(&self, path: &Path) -> io::Result<Box<dyn Contents + '_>>;
+}
+
+// the local filesystem
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Local;
+
+impl FileSource for Local {
+	fn stat(&self, path: &Path, follow_symlinks: bool) -> io::Result<Stat> {
+		let metadata = if follow_symlinks { path.metadata() } else { path.symlink_metadata() }?;
+		Ok(Stat::from_fs(&metadata))
+	}
+
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+		Ok(fs::read_dir(path)?.map(|entry| entry.map(|e| e.path())).collect())
+	}
+
+	fn read(&self, path: &Path) -> io::Result<Box<dyn Contents + '_>> {
+		Ok(Box::new(File::open(path)?))
+	}
+}
+
+// files and directories kept in memory, for tests and for trees made up by hand
+#[derive(Clone, Default, Debug)]
+pub struct Memory {
+	entries: BTreeMap<PathBuf, (Stat, Vec<u8>)>,
+	denied: BTreeSet<PathBuf>, // can be stat'ed but not listed or read, like without the read permission
+}
+
+impl Memory {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	// missing parents are added as directories
+	pub fn add_dir(&mut self, path: impl AsRef<Path>) -> &mut Self {
+		let path = path.as_ref();
+		if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !self.entries.contains_key(*p)) {
+			self.add_dir(parent);
+		}
+		let metadata = Metadata { mode: 0o040755, ..Default::default() };
+		self.entries.insert(path.to_owned(), (Stat::from_metadata(0, metadata), Vec::new()));
+		self
+	}
+
+	pub fn add_file(&mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> &mut Self {
+		let path = path.as_ref();
+		if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty() && !self.entries.contains_key(*p)) {
+			self.add_dir(parent);
+		}
+		let contents = contents.into();
+		let metadata = Metadata { mode: 0o100644, blocks: (contents.len() as u64).div_ceil(512), ..Default::default() };
+		self.entries.insert(path.to_owned(), (Stat::from_metadata(contents.len() as u64, metadata), contents));
+		self
+	}
+
+	pub fn deny(&mut self, path: impl AsRef<Path>) -> &mut Self {
+		self.denied.insert(path.as_ref().to_owned());
+		self
+	}
+
+	fn get(&self, path: &Path) -> io::Result<&(Stat, Vec<u8>)> {
+		self.entries.get(path).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such entry"))
+	}
+
+	fn get_readable(&self, path: &Path) -> io::Result<&(Stat, Vec<u8>)> {
+		let entry = self.get(path)?;
+		if self.denied.contains(path) {
+			return Err(io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"));
+		}
+		Ok(entry)
+	}
+}
+
+impl FileSource for Memory {
+	fn stat(&self, path: &Path, _follow_symlinks: bool) -> io::Result<Stat> {
+		self.get(path).map(|(stat, _)| *stat)
+	}
+
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+		if !self.get_readable(path)?.0.is_dir {
+			return Err(io::Error::new(io::ErrorKind::NotADirectory, "not a directory"));
+		}
+		Ok(self.entries.keys().filter(|p| p.parent() == Some(path)).map(|p| Ok(p.clone())).collect())
+	}
+
+	fn read(&self, path: &Path) -> io::Result<Box<dyn Contents + '_>> {
+		Ok(Box::new(Cursor::new(self.get_readable(path)?.1.as_slice())))
+	}
+}
+
+// a tree that is already known, served again as files: snapshots, ncdu dumps, du listings and archive listings
+// the contents aren't part of the tree, so nothing can be read
+#[derive(Clone, Debug)]
+pub struct Tree {
+	root: Node,
+}
+
+impl Tree {
+	pub fn new(root: Node) -> Self {
+		Self { root }
+	}
+
+	// a snapshot, ncdu dump or du listing
+	pub fn load(path: &Path, options: &ScanOptions) -> io::Result<Self> {
+		let root = scan::load_saved(path, options)
+			.unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::InvalidData, "not a saved scan")))?;
+		Ok(Self::new(root))
+	}
+
+	// the members of an archive, sized by their extracted length with the compressed size as the allocated blocks
+	pub fn archive(source: &impl FileSource, path: &Path) -> io::Result<Self> {
+		let kind = archive::Kind::from_path(path).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a known archive"))?;
+		let stat = source.stat(path, true)?;
+		let members = archive::list(source.read(path)?, kind)?;
+		Ok(Self::new(archive::tree(path, stat.len, stat.metadata, members, SizeMode::Apparent)))
+	}
+
+	fn find(&self, path: &Path) -> io::Result<&Node> {
+		self.root.find(&path.to_string_lossy()).ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such entry"))
+	}
+}
+
+impl FileSource for Tree {
+	fn stat(&self, path: &Path, _follow_symlinks: bool) -> io::Result<Stat> {
+		let entry = &self.find(path)?.entry;
+		Ok(Stat {
+			is_dir: entry.is_dir(),
+			is_file: !entry.is_dir() && entry.metadata().is_regular(),
+			len: if entry.is_dir() && !entry.is_archive() { 0 } else { entry.bytes() },
+			metadata: *entry.metadata(),
+		})
+	}
+
+	fn list(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+		let node = self.find(path)?;
+		if !node.entry.is_dir() {
+			return Err(io::Error::new(io::ErrorKind::NotADirectory, "not a directory"));
+		}
+		Ok(node.children.iter().map(|c| Ok(PathBuf::from(c.entry.get_full_path()))).collect())
+	}
+
+	fn read(&self, _path: &Path) -> io::Result<Box<dyn Contents + '_>> {
+		Err(io::Error::new(io::ErrorKind::Unsupported, "only the listing is known"))
+	}
+}
@@ -0,0 +1,67 @@
+// human readable renderings shared by the gui and the command line
+
+pub fn bytes_to_text(bytes: u64) -> String {
+	let units = ["", "K", "M", "G", "T", "P", "E"];
+	let order = if bytes == 0 { 0 } else { ((bytes as f32).log2() * 0.1) as usize };
+	let order = order.min(units.len() - 1);
+	let display_number = bytes as f32 / 1024f32.powf(order as f32);
+	let dn_digits = if display_number == 0.0 { 1 } else { display_number.log10() as usize + 1 };
+	format!("{:.2$}{}B", display_number, units[order], (3 - dn_digits.min(3)))
+}
+
+// unix timestamp to "YYYY-MM-DD HH:MM:SS" (UTC)
+pub fn time_to_text(seconds: i64) -> String {
+	let days = seconds.div_euclid(86400);
+	let day_seconds = seconds.rem_euclid(86400);
+
+	// civil from days, http://howardhinnant.github.io/date_algorithms.html
+	let z = days + 719468;
+	let era = z.div_euclid(146097);
+	let doe = z.rem_euclid(146097);
+	let yoe = (doe - doe/1460 + doe/36524 - doe/146096) / 365;
+	let doy = doe - (365*yoe + yoe/4 - yoe/100);
+	let mp = (5*doy + 2) / 153;
+	let day = doy - (153*mp + 2)/5 + 1;
+	let month = if mp < 10 { mp + 3 } else { mp - 9 };
+	let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+	format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, day_seconds / 3600, day_seconds / 60 % 60, day_seconds % 60)
+}
+
+// "YYYY-MM-DD HH:MM:SS" or the ISO 8601 "YYYY-MM-DDTHH:MM:SS.sssZ" (UTC) to a unix timestamp
+pub fn text_to_time(text: &str) -> Option<i64> {
+	let field = |range: std::ops::Range<usize>| text.get(range)?.parse::<i64>().ok();
+	let (year, month, day) = (field(0..4)?, field(5..7)?, field(8..10)?);
+	let (hours, minutes, seconds) = (field(11..13)?, field(14..16)?, field(17..19)?);
+	if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+		return None;
+	}
+
+	// days from civil, the inverse of the above
+	let y = if month <= 2 { year - 1 } else { year };
+	let era = y.div_euclid(400);
+	let yoe = y.rem_euclid(400);
+	let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+	let doe = yoe * 365 + yoe/4 - yoe/100 + doy;
+	let days = era * 146097 + doe - 719468;
+
+	Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// "1234", "10K", "1.5M" or "2GB" to bytes, with binary units like bytes_to_text
+pub fn text_to_bytes(text: &str) -> Option<u64> {
+	let text = text.trim();
+	let text = text.strip_suffix(['B', 'b']).unwrap_or(text);
+	let (number, unit) = match text.find(|c: char| c.is_ascii_alphabetic()) {
+		Some(i) => text.split_at(i),
+		None => (text, ""),
+	};
+
+	let order = ["", "K", "M", "G", "T", "P", "E"].iter().position(|u| u.eq_ignore_ascii_case(unit))?;
+	let number: f64 = number.trim().parse().ok()?;
+	if number < 0.0 {
+		return None;
+	}
+
+	Some((number * 1024f64.powi(order as i32)) as u64)
+}
@@ -0,0 +1,209 @@
+use std::{io::{Cursor, Write}, path::Path};
+
+use fsexpl_core::{archive::{self, Kind, Member}, metadata::Metadata, Node, TreeNode, SizeMode};
+
+// the 7z fixtures in tests/data are made with bsdtar --format 7zip, which compresses the header with lzma
+// (or lzma2 with --options 7zip:compression=lzma2, and not at all with 7zip:compression=store), from a.txt ("hello\n"),
+// dir/b.txt (3000 x) and an empty directory
+// huge-counts.7z and huge-sizes.7z are made up by hand: plain headers with coder counts and substream sizes near u64::MAX
+
+// a central directory entry, with a zip64 extra field when the sizes don't fit in 32 bits
+fn zip_entry(name: &str, bytes: u64, compressed: u64, mode: u32) -> Vec<u8> {
+	let zip64 = bytes >= 0xffffffff || compressed >= 0xffffffff;
+	let extra: Vec<u8> = if zip64 { [&1u16.to_le_bytes()[..], &16u16.to_le_bytes(), &bytes.to_le_bytes(), &compressed.to_le_bytes()].concat() } else { Vec::new() };
+	let small = |size: u64| if zip64 { 0xffffffff } else { size as u32 };
+
+	let mut entry = Vec::new();
+	entry.extend(0x02014b50u32.to_le_bytes());
+	entry.extend((3u16 << 8 | 45).to_le_bytes()); // made on unix
+	entry.extend(45u16.to_le_bytes());
+	entry.extend([0; 4]); // flags and method
+	entry.extend(0u16.to_le_bytes()); // time
+	entry.extend((40u16 << 9 | 1 << 5 | 1).to_le_bytes()); // 2020-01-01
+	entry.extend([0; 4]); // crc
+	entry.extend(small(compressed).to_le_bytes());
+	entry.extend(small(bytes).to_le_bytes());
+	entry.extend((name.len() as u16).to_le_bytes());
+	entry.extend((extra.len() as u16).to_le_bytes());
+	entry.extend([0; 6]); // comment length, disk, internal attributes
+	entry.extend((mode << 16).to_le_bytes());
+	entry.extend(0u32.to_le_bytes()); // local header offset, never read
+	entry.extend(name.as_bytes());
+	entry.extend(extra);
+	entry
+}
+
+// only a central directory, which is all the listing reads
+fn zip(entries: &[Vec<u8>], zip64: bool, comment: &str) -> Vec<u8> {
+	let directory = entries.concat();
+	let mut file = directory.clone();
+
+	if zip64 {
+		let record_offset = file.len() as u64;
+		file.extend(0x06064b50u32.to_le_bytes());
+		file.extend(44u64.to_le_bytes());
+		file.extend([0; 12]); // versions and disk numbers
+		file.extend((entries.len() as u64).to_le_bytes());
+		file.extend((entries.len() as u64).to_le_bytes());
+		file.extend((directory.len() as u64).to_le_bytes());
+		file.extend(0u64.to_le_bytes());
+
+		file.extend(0x07064b50u32.to_le_bytes());
+		file.extend(0u32.to_le_bytes());
+		file.extend(record_offset.to_le_bytes());
+		file.extend(1u32.to_le_bytes());
+	}
+
+	let count = if zip64 { 0xffff } else { entries.len() as u16 };
+	file.extend(0x06054b50u32.to_le_bytes());
+	file.extend([0; 4]);
+	file.extend(count.to_le_bytes());
+	file.extend(count.to_le_bytes());
+	file.extend((directory.len() as u32).to_le_bytes());
+	file.extend(if zip64 { 0xffffffff } else { 0u32 }.to_le_bytes());
+	file.extend((comment.len() as u16).to_le_bytes());
+	file.extend(comment.as_bytes());
+	file
+}
+
+fn tree(members: Vec<Member>) -> Node {
+	archive::tree(Path::new("/a.zip"), 1000, Metadata { mode: 0o100644, ..Default::default() }, members, SizeMode::Apparent)
+}
+
+fn names(node: &Node) -> Vec<String> {
+	let mut names: Vec<String> = node.children.iter().map(|c| c.entry.name().to_string()).collect();
+	names.sort();
+	names
+}
+
+#[test]
+fn zip64_with_more_entries_than_fit_in_16_bits() {
+	let mut entries: Vec<Vec<u8>> = (0..70000).map(|i| zip_entry(&format!("files/{i}"), 10, 5, 0o100644)).collect();
+	entries.push(zip_entry("huge.iso", 5 << 32, 1 << 32, 0o100644));
+
+	let members = archive::list(Cursor::new(zip(&entries, true, "")), Kind::Zip).unwrap();
+	assert_eq!(members.len(), 70001);
+	assert_eq!((members[69999].path.as_str(), members[69999].bytes, members[69999].compressed), ("files/69999", 10, 5));
+	assert_eq!((members[70000].bytes, members[70000].compressed), (5 << 32, 1 << 32));
+	assert_eq!(members[0].modified, 1577836800);
+
+	let root = tree(members);
+	assert_eq!(root.entry.files(), 70001);
+	assert_eq!(root.find("/a.zip/files").unwrap().entry.bytes(), 700000);
+}
+
+#[test]
+fn zip_with_a_comment() {
+	let entries = [zip_entry("a.txt", 6, 6, 0o100644), zip_entry("dir/", 0, 0, 0o040755)];
+	// a comment that looks like the start of another end record mustn't confuse the search from the back
+	let comment = "exported by a test, PK\x05\x06 inside";
+
+	let members = archive::list(Cursor::new(zip(&entries, false, comment)), Kind::Zip).unwrap();
+	assert_eq!(members.len(), 2);
+	assert_eq!(members[0].path, "a.txt");
+	assert!(members[1].is_dir);
+}
+
+#[test]
+fn paths_leaving_the_archive_are_dropped() {
+	let entries = ["../evil", "a/../../b", "./c", "d//e", "/f"].map(|name| zip_entry(name, 1, 1, 0o100644));
+	let members = archive::list(Cursor::new(zip(&entries, false, "")), Kind::Zip).unwrap();
+
+	let root = tree(members);
+	assert_eq!(names(&root), ["c", "d", "f"]);
+	assert_eq!(names(root.find("/a.zip/d").unwrap()), ["e"]);
+	assert_eq!(root.entry.files(), 3);
+}
+
+#[test]
+fn tar_gz() {
+	let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default()));
+	for (path, contents) in [("./top.txt", &b"hello"[..]), ("./sub/inner.txt", &[7; 100_000][..])] {
+		let mut header = tar::Header::new_gnu();
+		header.set_size(contents.len() as u64);
+		header.set_mode(0o640);
+		header.set_mtime(1_600_000_000);
+		builder.append_data(&mut header, path, contents).unwrap();
+	}
+	let mut file = builder.into_inner().unwrap().finish().unwrap();
+	file.flush().unwrap();
+	let length = file.len() as u64;
+
+	let members = archive::list(Cursor::new(file), Kind::TarGz).unwrap();
+	assert_eq!(members.len(), 2);
+	assert_eq!(members[0].mode, 0o100640);
+	assert_eq!(members[0].modified, 1_600_000_000);
+	// the compressed size is shared out by the extracted sizes
+	assert!(members.iter().map(|m| m.compressed).sum::<u64>() <= length);
+	assert!(members[1].compressed > members[0].compressed);
+
+	let root = archive::tree(Path::new("/t.tgz"), length, Metadata { mode: 0o100644, ..Default::default() }, members, SizeMode::Apparent);
+	assert_eq!(names(&root), ["sub", "top.txt"]);
+	assert_eq!(root.find("/t.tgz/sub").unwrap().entry.bytes(), 100_000);
+	// the archive keeps its own length for the directories above it
+	assert_eq!(root.entry.bytes(), length);
+}
+
+#[test]
+fn seven_zip_with_encoded_and_plain_headers() {
+	for fixture in [&include_bytes!("data/header-lzma.7z")[..], &include_bytes!("data/header-lzma2.7z")[..], &include_bytes!("data/header-plain.7z")[..]] {
+		let mut members = archive::list(Cursor::new(fixture), Kind::SevenZip).unwrap();
+		members.sort_by(|a, b| a.path.cmp(&b.path));
+
+		let listed: Vec<(&str, bool, u64)> = members.iter().map(|m| (m.path.as_str(), m.is_dir, m.bytes)).collect();
+		assert_eq!(listed, [("a.txt", false, 6), ("dir", true, 0), ("dir/b.txt", false, 3000), ("empty", true, 0)]);
+		assert_eq!(members[0].modified, 1577934245);
+	}
+}
+
+#[test]
+fn truncated_archives_are_errors() {
+	let zip = zip(&[zip_entry("a.txt", 6, 6, 0o100644)], false, "");
+	let seven_zip = include_bytes!("data/header-lzma.7z");
+	let mut builder = tar::Builder::new(Vec::new());
+	let mut header = tar::Header::new_gnu();
+	header.set_size(5000);
+	builder.append_data(&mut header, "a.txt", &[1; 5000][..]).unwrap();
+	let tar = builder.into_inner().unwrap();
+	let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+	encoder.write_all(&tar).unwrap();
+	let tar_gz = encoder.finish().unwrap();
+
+	for (file, kind) in [(&zip[..], Kind::Zip), (&seven_zip[..], Kind::SevenZip), (&tar[..], Kind::Tar), (&tar_gz[..], Kind::TarGz)] {
+		// an empty file is an empty tar, and one cut in its end marker still has all its members
+		for length in [0, 1, 10, 31, file.len() / 2, file.len() - 1].into_iter().filter(|&length| kind != Kind::Tar || (1..5000).contains(&length)) {
+			assert!(archive::list(Cursor::new(&file[..length]), kind).is_err(), "{kind:?} cut at {length}");
+		}
+	}
+}
+
+#[test]
+fn seven_zip_with_impossible_counts() {
+	for fixture in [&include_bytes!("data/huge-counts.7z")[..], &include_bytes!("data/huge-sizes.7z")[..]] {
+		assert!(archive::list(Cursor::new(fixture), Kind::SevenZip).is_err());
+	}
+}
+
+#[test]
+fn corrupt_archives_dont_panic() {
+	// flips in the plain header reach the header parser, those in the compressed one mostly the decoder
+	// the plain one is flipped from its signature header on and in its last 228 bytes, the header itself
+	let plain = include_bytes!("data/header-plain.7z");
+	let lzma2 = include_bytes!("data/header-lzma2.7z");
+	for (seven_zip, positions) in [(&lzma2[..], (0..lzma2.len()).collect::<Vec<_>>()), (&plain[..], (0..32).chain(plain.len() - 228..plain.len()).collect())] {
+		for at in positions {
+			for flip in [0x01, 0x80, 0xff] {
+				let mut corrupt = seven_zip.to_vec();
+				corrupt[at] ^= flip;
+				let _ = archive::list(Cursor::new(corrupt), Kind::SevenZip);
+			}
+		}
+	}
+
+	let zip = zip(&[zip_entry("a.txt", 6, 6, 0o100644), zip_entry("big", 5 << 32, 1, 0o100644)], true, "");
+	for at in 0..zip.len() {
+		let mut corrupt = zip.clone();
+		corrupt[at] ^= 0xff;
+		let _ = archive::list(Cursor::new(corrupt), Kind::Zip);
+	}
+}
@@ -0,0 +1,82 @@
+use std::{env, io::Write, path::Path, process::{self, Command, Stdio}};
+
+use fsexpl_core::{scan::{self, ScanOptions}, metadata::StorageClass, Node, TreeNode};
+
+// runs against minio or any other store speaking the s3 api, for example
+//   docker run -p 9000:9000 minio/minio server /data, and a bucket made with mc mb
+//   FSEXPL_TEST_S3_BUCKET=test AWS_ENDPOINT_URL=http://localhost:9000 AWS_ACCESS_KEY_ID=minioadmin AWS_SECRET_ACCESS_KEY=minioadmin \
+//     cargo test -- --ignored
+// the objects are written below a prefix of their own and removed afterwards
+
+struct Bucket {
+	url: String, // the endpoint with the bucket
+	region: String,
+	credentials: Option<String>, // key id and secret for curl --user
+}
+
+impl Bucket {
+	fn from_env() -> Self {
+		let variable = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+		let bucket = variable("FSEXPL_TEST_S3_BUCKET").expect("FSEXPL_TEST_S3_BUCKET isn't set");
+		let region = variable("AWS_REGION").or_else(|| variable("AWS_DEFAULT_REGION")).unwrap_or_else(|| "us-east-1".to_string());
+		let endpoint = variable("AWS_ENDPOINT_URL").unwrap_or_else(|| format!("https://s3.{region}.amazonaws.com"));
+		let credentials = variable("AWS_ACCESS_KEY_ID").zip(variable("AWS_SECRET_ACCESS_KEY")).map(|(id, secret)| format!("{id}:{secret}"));
+		Self { url: format!("{}/{bucket}", endpoint.trim_end_matches('/')), region, credentials }
+	}
+
+	fn curl(&self, method: &str, key: &str, body: &[u8]) {
+		let mut command = Command::new("curl");
+		command.args(["--silent", "--show-error", "--fail", "--request", method, &format!("{}/{key}", self.url)]);
+		if let Some(credentials) = &self.credentials {
+			command.args(["--aws-sigv4", &format!("aws:amz:{}:s3", self.region), "--user", credentials]);
+		}
+		if method == "PUT" {
+			// with a length, s3 doesn't take chunked uploads
+			command.args(["--data-binary", "@-"]);
+		}
+
+		let mut child = command.stdin(Stdio::piped()).spawn().unwrap();
+		child.stdin.take().unwrap().write_all(body).unwrap();
+		assert!(child.wait().unwrap().success(), "{method} {key}");
+	}
+}
+
+fn names(node: &Node) -> Vec<String> {
+	let mut names: Vec<String> = node.children.iter().map(|c| c.entry.name().to_string()).collect();
+	names.sort();
+	names
+}
+
+#[test]
+#[ignore = "needs a bucket in FSEXPL_TEST_S3_BUCKET"]
+fn scan_a_bucket() {
+	let bucket = Bucket::from_env();
+	let prefix = format!("fsexpl-test-{}", process::id());
+	let objects: [(&str, &[u8]); 5] = [
+		("a.txt", &[1; 1000]),
+		("dir", &[2; 5]), // beside the keys below dir/
+		("dir/b", &[3; 300]),
+		("dir/deep/c", &[4; 20]),
+		("folder/", &[]), // what consoles create for an empty folder
+	];
+	for (key, body) in objects {
+		bucket.curl("PUT", &format!("{prefix}/{key}"), body);
+	}
+
+	let url = format!("s3://{}/{prefix}", env::var("FSEXPL_TEST_S3_BUCKET").unwrap());
+	let mut denied = Vec::new();
+	let root = scan::wait(scan::spawn(Path::new(&url), &ScanOptions::default()), &mut denied);
+	for (key, _) in objects {
+		bucket.curl("DELETE", &format!("{prefix}/{key}"), &[]);
+	}
+
+	let root = root.unwrap();
+	assert!(denied.is_empty(), "{denied:?}");
+	assert_eq!(root.entry.bytes(), 1325);
+	assert_eq!(root.entry.files(), 4);
+	assert_eq!(names(&root), ["a.txt", "dir", "dir (object)", "folder"]);
+	assert_eq!(root.find(&format!("{url}/dir")).unwrap().entry.bytes(), 320);
+	assert!(root.find(&format!("{url}/folder")).unwrap().entry.is_dir());
+	let object = root.find(&format!("{url}/dir (object)")).unwrap();
+	assert_eq!((object.entry.bytes(), object.entry.metadata().storage_class), (5, Some(StorageClass::Standard)));
+}
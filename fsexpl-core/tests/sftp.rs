@@ -0,0 +1,44 @@
+#![cfg(unix)]
+
+use std::{env, fs, path::{Path, PathBuf}, process::{self, Command}};
+
+use fsexpl_core::{scan::{self, ScanOptions}, sftp::Sftp, TreeNode};
+
+// the server ssh runs for the sftp subsystem, installed with openssh in one of these places
+fn sftp_server() -> Option<PathBuf> {
+	let installed = ["/usr/lib/openssh/sftp-server", "/usr/libexec/openssh/sftp-server", "/usr/libexec/sftp-server", "/usr/lib/ssh/sftp-server"].map(PathBuf::from);
+	let on_path = env::var_os("PATH").into_iter().flat_map(|path| env::split_paths(&path).collect::<Vec<_>>()).map(|dir| dir.join("sftp-server"));
+	installed.into_iter().chain(on_path).find(|path| path.is_file())
+}
+
+// run with cargo test -- --ignored where openssh is installed
+#[test]
+#[ignore = "needs sftp-server from openssh"]
+fn scan_through_sftp_server() {
+	let server = sftp_server().expect("sftp-server isn't installed");
+
+	let dir = env::temp_dir().join(format!("fsexpl-sftp-{}", process::id()));
+	fs::create_dir_all(dir.join("sub/deep")).unwrap();
+	fs::write(dir.join("a.txt"), [1; 1000]).unwrap();
+	fs::write(dir.join("sub/b.bin"), [2; 300]).unwrap();
+	fs::write(dir.join("sub/deep/c"), [3; 20]).unwrap();
+	// a link to nothing can't be stat'ed when following links, even as root
+	std::os::unix::fs::symlink(dir.join("missing"), dir.join("dangling")).unwrap();
+	// links back up are skipped like on the local disk, sftp has no inodes to tell them by
+	std::os::unix::fs::symlink("..", dir.join("sub/deep/up")).unwrap();
+	std::os::unix::fs::symlink(".", dir.join("sub/here")).unwrap();
+
+	let sftp = Sftp::spawn(Command::new(server), "sftp://test").unwrap();
+	let root = PathBuf::from(format!("sftp://test{}", dir.canonicalize().unwrap().to_string_lossy()));
+	let mut denied = Vec::new();
+	let node = scan::wait(scan::spawn_source(sftp, &root, &ScanOptions::default()), &mut denied);
+	fs::remove_dir_all(&dir).unwrap();
+
+	let node = node.unwrap();
+	assert_eq!(node.entry.bytes(), 1320);
+	assert_eq!(node.entry.files(), 3);
+	let deep = root.join("sub/deep");
+	assert_eq!(node.find(&deep.to_string_lossy()).unwrap().entry.bytes(), 20);
+	assert_eq!(denied, [root.join("dangling")]);
+	assert!(Path::new(&node.entry.get_full_path()).starts_with("sftp://test/"));
+}
@@ -0,0 +1,93 @@
+use std::path::{Path, PathBuf};
+
+use fsexpl_core::{scan::{self, ScanOptions}, source::{Memory, Tree}, FileSource, Node, TreeNode};
+
+fn memory() -> Memory {
+	let mut memory = Memory::new();
+	memory
+		.add_file("/m/a.txt", vec![1; 1000])
+		.add_file("/m/sub/b.bin", vec![2; 300])
+		.add_file("/m/sub/deep/c", vec![3; 20])
+		.add_file("/m/cache/d", vec![4; 5000])
+		.add_file("/m/locked/e", vec![5; 7])
+		.add_dir("/m/empty");
+	memory
+}
+
+fn scan(source: impl FileSource + Send + 'static, options: &ScanOptions) -> (Option<Node>, Vec<PathBuf>) {
+	let mut denied = Vec::new();
+	let node = scan::wait(scan::spawn_source(source, Path::new("/m"), options), &mut denied);
+	(node, denied)
+}
+
+fn names(node: &Node) -> Vec<String> {
+	let mut names: Vec<String> = node.children.iter().map(|c| c.entry.name().to_string()).collect();
+	names.sort();
+	names
+}
+
+#[test]
+fn totals() {
+	let (root, denied) = scan(memory(), &ScanOptions::default());
+	let root = root.unwrap();
+
+	assert!(denied.is_empty());
+	assert_eq!(root.entry.bytes(), 6327);
+	assert_eq!(root.entry.files(), 5);
+	assert_eq!(names(&root), ["a.txt", "cache", "empty", "locked", "sub"]);
+	assert_eq!(root.find("/m/sub").unwrap().entry.bytes(), 320);
+	assert_eq!(root.find("/m/sub/deep/c").unwrap().entry.bytes(), 20);
+	// the largest entry last
+	assert_eq!(root.children.last().unwrap().entry.name(), "cache");
+}
+
+#[test]
+fn excludes() {
+	// a full path, and a bare name matched anywhere
+	let options = ScanOptions { excludes: vec![PathBuf::from("/m/cache"), PathBuf::from("deep")], ..Default::default() };
+	let root = scan(memory(), &options).0.unwrap();
+
+	assert_eq!(names(&root), ["a.txt", "empty", "locked", "sub"]);
+	assert_eq!(names(root.find("/m/sub").unwrap()), ["b.bin"]);
+	assert_eq!(root.entry.bytes(), 1307);
+	assert_eq!(root.entry.files(), 3);
+}
+
+#[test]
+fn denied_paths() {
+	let mut memory = memory();
+	memory.deny("/m/locked");
+	let (root, denied) = scan(memory, &ScanOptions::default());
+	let root = root.unwrap();
+
+	// the directory is left out and reported, the rest is scanned
+	assert_eq!(denied, [PathBuf::from("/m/locked")]);
+	assert!(root.find("/m/locked").is_none());
+	assert_eq!(root.entry.bytes(), 6320);
+	assert_eq!(root.entry.files(), 4);
+
+	// a root that can't be read at all is no tree
+	let mut memory = Memory::new();
+	memory.add_dir("/m").deny("/m");
+	let (root, denied) = scan(memory, &ScanOptions::default());
+	assert!(root.is_none());
+	assert_eq!(denied, [PathBuf::from("/m")]);
+}
+
+#[test]
+fn tree_scanned_again() {
+	let first = scan(memory(), &ScanOptions::default()).0.unwrap();
+
+	// the same totals and entries from the tree itself, as when a saved scan is opened
+	let again = scan(Tree::new(first.clone()), &ScanOptions::default()).0.unwrap();
+	assert_eq!((again.entry.bytes(), again.entry.files()), (first.entry.bytes(), first.entry.files()));
+	assert_eq!(names(&again), names(&first));
+	assert_eq!(names(again.find("/m/sub").unwrap()), ["b.bin", "deep"]);
+	assert_eq!(again.find("/m/sub/deep/c").unwrap().entry.bytes(), 20);
+	assert!(again.find("/m/empty").unwrap().entry.is_dir());
+
+	// and the options apply to it like to any other source
+	let options = ScanOptions { excludes: vec![PathBuf::from("/m/sub")], ..Default::default() };
+	let excluded = scan(Tree::new(first), &options).0.unwrap();
+	assert_eq!(excluded.entry.bytes(), 6007);
+}
@@ -0,0 +1,188 @@
+use std::{env, path::PathBuf, process};
+
+use crate::render::ImageFormat;
+
+use fsexpl_core::{scan::{ScanOptions, SizeMode}, du::{Units, DirSizes}, export::{self, Format, Filter}, csv::{Column, Rows}, text::text_to_bytes};
+
+const USAGE: &str = "\
+usage: fsexpl [OPTIONS] [PATH]
+       fsexpl report [OPTIONS] [--top N] [--depth N] [PATH]
+       fsexpl export [OPTIONS] [--format FORMAT] [--out FILE] [EXPORT OPTIONS] [PATH]
+       fsexpl render [OPTIONS] --out IMAGE [RENDER OPTIONS] [PATH]
+
+Scans PATH right away when it is given, PATH can also be a saved snapshot,
+an ncdu -o dump, the output of du -ab or du -ak, sftp://[user@]host[:port]/path
+to scan a remote host through ssh, or s3://bucket/prefix to scan object storage.
+The report command prints the largest entries as an indented tree, the
+export command writes the scan in another format and the render command
+draws the treemap to an image, all without opening a window.
+
+options:
+  -x, --one-file-system   skip directories on other filesystems
+  -L, --follow-symlinks   follow symbolic links (default)
+  -P, --no-follow-symlinks
+                          count symbolic links as entries instead of following them
+      --archives          list the contents of .zip, .tar, .tar.gz, .tar.zst and .7z files
+      --exclude PATH      skip a full path, or every entry with that name (repeatable)
+      --size MODE         apparent (file lengths, default) or disk (allocated blocks)
+      --du-units UNIT     b (du -ab, default) or k (du -ak) for du listings
+      --du-dirs SIZES     children (default) sums up the entries below directories,
+                          listed uses the totals du printed for them
+  -h, --help              print this message
+
+report options:
+      --top N             list at most N entries per directory (default 20)
+      --depth N           descend at most N levels below PATH (default 1)
+
+export options:
+      --format FORMAT     json (default), csv, tsv, ncdu or html
+      --out FILE          where to write the export, standard output by default
+      --depth N           leave out entries more than N levels below PATH
+      --min-size SIZE     leave out entries smaller than SIZE, like 100K or 2G
+      --columns LIST      csv and tsv columns, comma separated, out of path, depth,
+                          bytes, allocated, files, extension, modified and owner (default all)
+      --rows ROWS         csv and tsv rows for all (default), files or directories
+
+render options:
+      --out IMAGE         where to write the image, an .svg or .png file
+      --depth N           split directories at most N levels below PATH (default 3)
+      --min-size SIZE     merge entries smaller than SIZE into one tile per directory
+      --image-size WxH    the image size in pixels (default 1920x1080)";
+
+pub enum Mode {
+	Gui,
+	Report { top: usize, depth: usize },
+	Export { format: Format, out: Option<PathBuf>, options: export::Options },
+	Render { out: PathBuf, filter: Filter, width: u32, height: u32 },
+}
+
+pub struct Args {
+	pub mode: Mode,
+	pub path: Option<PathBuf>,
+	pub options: ScanOptions,
+}
+
+impl Args {
+	// prints the usage and exits on invalid arguments
+	pub fn from_env() -> Self {
+		match parse(env::args().skip(1)) {
+			Ok(Some(args)) => args,
+			Ok(None) => {
+				println!("{USAGE}");
+				process::exit(0);
+			},
+			Err(e) => {
+				eprintln!("fsexpl: {e}\n\n{USAGE}");
+				process::exit(2);
+			},
+		}
+	}
+}
+
+// None if help was requested
+fn parse(args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+	let mut args = args.peekable();
+	let mut mode = Mode::Gui;
+	let mut path = None;
+	let mut options = ScanOptions::default();
+
+	match args.peek().map(String::as_str) {
+		Some("report") => mode = Mode::Report { top: 20, depth: 1 },
+		Some("export") => mode = Mode::Export { format: Format::Json, out: None, options: export::Options::default() },
+		Some("render") => {
+			let filter = Filter { max_depth: Some(3), min_bytes: 0 };
+			mode = Mode::Render { out: PathBuf::new(), filter, width: 1920, height: 1080 };
+		},
+		_ => {},
+	}
+	if !matches!(mode, Mode::Gui) {
+		args.next();
+	}
+
+	while let Some(arg) = args.next() {
+		match (arg.as_str(), &mut mode) {
+			("-h" | "--help", _) => return Ok(None),
+			("-x" | "--one-file-system", _) => options.one_filesystem = true,
+			("-L" | "--follow-symlinks", _) => options.follow_symlinks = true,
+			("-P" | "--no-follow-symlinks", _) => options.follow_symlinks = false,
+			("--archives", _) => options.archives = true,
+			("--exclude", _) => {
+				let value = args.next().ok_or("--exclude needs a path")?;
+				options.excludes.push(PathBuf::from(value));
+			},
+			("--size", _) => {
+				options.size_mode = match args.next().as_deref() {
+					Some("apparent") => SizeMode::Apparent,
+					Some("disk") => SizeMode::Disk,
+					_ => return Err("--size needs apparent or disk".into()),
+				};
+			},
+			("--du-units", _) => {
+				options.du.units = match args.next().as_deref() {
+					Some("b") => Units::Bytes,
+					Some("k") => Units::Kilobytes,
+					_ => return Err("--du-units needs b or k".into()),
+				};
+			},
+			("--du-dirs", _) => {
+				options.du.dir_sizes = match args.next().as_deref() {
+					Some("children") => DirSizes::Children,
+					Some("listed") => DirSizes::Listed,
+					_ => return Err("--du-dirs needs children or listed".into()),
+				};
+			},
+			("--top", Mode::Report { top, .. }) => *top = number(&arg, args.next())?,
+			("--depth", Mode::Report { depth, .. }) => *depth = number(&arg, args.next())?,
+			("--out", Mode::Export { out, .. }) => *out = Some(PathBuf::from(args.next().ok_or("--out needs a file")?)),
+			("--format", Mode::Export { format, .. }) => {
+				*format = args.next().as_deref().and_then(Format::from_name).ok_or("--format needs json, csv, tsv, ncdu or html")?;
+			},
+			("--depth", Mode::Export { options, .. }) => options.filter.max_depth = Some(number(&arg, args.next())?),
+			("--min-size", Mode::Export { options, .. }) => {
+				options.filter.min_bytes = args.next().as_deref().and_then(text_to_bytes).ok_or("--min-size needs a size like 100K")?;
+			},
+			("--out", Mode::Render { out, .. }) => *out = PathBuf::from(args.next().ok_or("--out needs an image file")?),
+			("--depth", Mode::Render { filter, .. }) => filter.max_depth = Some(number(&arg, args.next())?),
+			("--min-size", Mode::Render { filter, .. }) => {
+				filter.min_bytes = args.next().as_deref().and_then(text_to_bytes).ok_or("--min-size needs a size like 100K")?;
+			},
+			("--image-size", Mode::Render { width, height, .. }) => {
+				(*width, *height) = args.next().as_deref()
+					.and_then(|value| value.split_once('x'))
+					.and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+					.filter(|&(w, h)| (1..=16384).contains(&w) && (1..=16384).contains(&h))
+					.ok_or("--image-size needs a size in pixels like 1920x1080")?;
+			},
+			("--columns", Mode::Export { options, .. }) => {
+				let list = args.next().ok_or("--columns needs a list")?;
+				options.columns = list.split(',')
+					.map(|name| Column::from_name(name.trim()).ok_or(format!("unknown column {name}")))
+					.collect::<Result<_, _>>()?;
+			},
+			("--rows", Mode::Export { options, .. }) => {
+				options.rows = args.next().as_deref().and_then(Rows::from_name).ok_or("--rows needs all, files or directories")?;
+			},
+			_ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown option {arg}")),
+			_ if path.is_some() => return Err(format!("unexpected argument {arg}")),
+			_ => path = Some(PathBuf::from(arg)),
+		}
+	}
+
+	if matches!(&mode, Mode::Render { out, .. } if ImageFormat::from_path(out).is_none()) {
+		return Err("render needs --out IMAGE ending in .svg or .png".into());
+	}
+
+	Ok(Some(Args { mode, path, options }))
+}
+
+fn number(option: &str, value: Option<String>) -> Result<usize, String> {
+	value.and_then(|v| v.parse().ok()).ok_or_else(|| format!("{option} needs a number"))
+}
+
+// the current directory, or the home directory if that is gone
+pub fn default_path() -> PathBuf {
+	env::current_dir()
+		.ok()
+		.or_else(|| env::var_os("HOME").map(PathBuf::from))
+		.unwrap_or_else(|| PathBuf::from("/"))
+}
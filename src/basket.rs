@@ -0,0 +1,165 @@
+use std::{thread, sync::mpsc::{channel, Receiver, Sender}, path::PathBuf};
+
+use crate::{trash::{self, TrashedItem}, helper::remove_path};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+	Delete,
+	Trash,
+}
+
+#[derive(Debug)]
+pub enum Message {
+	Done(PathBuf, Result<Option<TrashedItem>, String>), // trashed item if the action was a trash move
+	Finished,
+}
+
+pub enum Execution {
+	Idle,
+	Running(usize, usize, Receiver<Message>), // done, total, message receiver
+}
+
+// paths marked for removal, never nested in each other
+pub struct Basket {
+	items: Vec<(String, u64)>, // full path, bytes when marked
+	pub action: Action,
+	execution: Execution,
+	pub succeeded: Vec<PathBuf>,
+	pub failed: Vec<(PathBuf, String)>,
+}
+
+impl Basket {
+	pub fn new() -> Self {
+		Self {
+			items: Vec::new(),
+			action: Action::Trash,
+			execution: Execution::Idle,
+			succeeded: Vec::new(),
+			failed: Vec::new(),
+		}
+	}
+
+	pub fn items(&self) -> &[(String, u64)] {
+		&self.items
+	}
+
+	pub fn contains(&self, path: &str) -> bool {
+		self.items.iter().any(|(p, _)| p == path)
+	}
+
+	// true if the path or one of its ancestors is marked
+	pub fn covers(&self, path: &str) -> bool {
+		self.items.iter().any(|(p, _)| is_within(path, p))
+	}
+
+	// marking a directory replaces its already marked descendants
+	pub fn add(&mut self, path: String, bytes: u64) {
+		if self.covers(&path) {
+			return;
+		}
+		self.items.retain(|(p, _)| !is_within(p, &path));
+		self.items.push((path, bytes));
+	}
+
+	pub fn remove(&mut self, path: &str) {
+		self.items.retain(|(p, _)| p != path);
+	}
+
+	pub fn clear(&mut self) {
+		self.items.clear();
+	}
+
+	pub fn reclaimable(&self) -> u64 {
+		self.items.iter().map(|(_, bytes)| bytes).sum()
+	}
+
+	// what execute() would do, without touching anything
+	pub fn dry_run(&self) -> Vec<String> {
+		let verb = match self.action {
+			Action::Delete => "delete",
+			Action::Trash => "move to trash",
+		};
+		self.items.iter().map(|(path, _)| format!("{verb} {path}")).collect()
+	}
+
+	pub fn execute(&mut self) {
+		if self.is_running() || self.items.is_empty() {
+			return;
+		}
+
+		let (mut sender, receiver) = channel::<Message>();
+
+		let paths: Vec<PathBuf> = self.items.drain(..).map(|(p, _)| PathBuf::from(p)).collect();
+		let total = paths.len();
+		let action = self.action;
+		thread::spawn(move || {
+			remove_all(paths, action, &mut sender);
+
+			sender.send(Message::Finished).unwrap();
+		});
+
+		self.succeeded.clear();
+		self.failed.clear();
+		self.execution = Execution::Running(0, total, receiver);
+	}
+
+	// returns the paths removed since the last call, with their trash entries if they were trashed
+	pub fn process(&mut self) -> Vec<(PathBuf, Option<TrashedItem>)> {
+		let mut removed = Vec::new();
+		let mut finished = false;
+
+		if let Execution::Running(done, _, receiver) = &mut self.execution {
+			while let Ok(m) = receiver.try_recv() {
+				match m {
+					Message::Done(path, Ok(item)) => {
+						*done += 1;
+						self.succeeded.push(path.clone());
+						removed.push((path, item));
+					},
+					Message::Done(path, Err(e)) => {
+						*done += 1;
+						self.failed.push((path, e));
+					},
+					Message::Finished => finished = true,
+				}
+			}
+		}
+
+		if finished {
+			self.execution = Execution::Idle;
+		}
+
+		removed
+	}
+
+	pub fn is_running(&self) -> bool {
+		matches!(self.execution, Execution::Running(_, _, _))
+	}
+
+	// (done, total) while running
+	pub fn progress(&self) -> Option<(usize, usize)> {
+		match &self.execution {
+			Execution::Idle => None,
+			Execution::Running(done, total, _) => Some((*done, *total)),
+		}
+	}
+}
+
+fn remove_all(paths: Vec<PathBuf>, action: Action, sender: &mut Sender<Message>) {
+	for path in paths {
+		let result = match action {
+			Action::Delete => remove_path(&path).map(|_| None),
+			Action::Trash => trash::move_to_trash(&path).map(Some),
+		};
+
+		sender.send(Message::Done(path, result.map_err(|e| e.to_string()))).unwrap();
+	}
+}
+
+// true if path is ancestor or lies below it
+fn is_within(path: &str, ancestor: &str) -> bool {
+	match path.strip_prefix(ancestor) {
+		Some(rest) => rest.is_empty() || rest.starts_with(['/', '\\']),
+		None => false,
+	}
+}
\ No newline at end of file
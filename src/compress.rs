@@ -0,0 +1,231 @@
+use std::{thread, sync::mpsc::{channel, Receiver, Sender}, path::{Path, PathBuf}, fs::{self, File}, io::{self, Read, BufWriter}, collections::{HashMap, hash_map::DefaultHasher}, hash::Hasher};
+
+use crate::helper::remove_path;
+
+const LEVEL: i32 = 3;
+const SAMPLE_BYTES_PER_FILE: usize = 64 * 1024;
+const SAMPLE_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum Message {
+	Estimate(u64), // expected archive size
+	Progress(u64), // bytes archived
+	Finished(Result<u64, String>), // archive size
+}
+
+pub enum Status {
+	Running(u64, Receiver<Message>), // bytes archived, message receiver
+	Done(Result<u64, String>), // archive size
+}
+
+// packs a directory into a .tar.zst next to it, optionally removing the directory once the archive is verified
+pub struct Compression {
+	pub source: PathBuf,
+	pub archive: PathBuf,
+	pub total: u64,
+	pub estimate: Option<u64>,
+	pub delete_original: bool,
+	pub status: Status,
+}
+
+impl Compression {
+	pub fn start(source: &Path, total: u64, delete_original: bool) -> Self {
+		let (mut sender, receiver) = channel::<Message>();
+
+		let mut archive = source.as_os_str().to_owned();
+		archive.push(".tar.zst");
+		let archive = PathBuf::from(archive);
+
+		let (source_buf, archive_buf) = (source.to_owned(), archive.clone());
+		thread::spawn(move || {
+			let result = compress(&source_buf, &archive_buf, total, delete_original, &mut sender).map_err(|e| e.to_string());
+
+			sender.send(Message::Finished(result)).unwrap();
+		});
+
+		Self {
+			source: source.to_owned(),
+			archive,
+			total,
+			estimate: None,
+			delete_original,
+			status: Status::Running(0, receiver),
+		}
+	}
+
+	// returns true if the compression finished, successfully or not
+	pub fn process(&mut self) -> bool {
+		let mut result = None;
+
+		if let Status::Running(bytes, receiver) = &mut self.status {
+			while let Ok(m) = receiver.try_recv() {
+				match m {
+					Message::Estimate(estimate) => self.estimate = Some(estimate),
+					Message::Progress(new_bytes) => *bytes = new_bytes,
+					Message::Finished(r) => result = Some(r),
+				}
+			}
+		}
+
+		if let Some(result) = result {
+			self.status = Status::Done(result);
+			return true;
+		}
+
+		false
+	}
+}
+
+fn compress(source: &Path, archive: &Path, total: u64, delete_original: bool, sender: &mut Sender<Message>) -> io::Result<u64> {
+	if archive.symlink_metadata().is_ok() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", archive.to_string_lossy())));
+	}
+
+	let mut files = Vec::new();
+	collect_files(source, &mut files)?;
+
+	sender.send(Message::Estimate(estimate(&files, total)?)).unwrap();
+
+	let result = write_archive(source, archive, sender).and_then(|hashes| verify(archive, &hashes));
+	if let Err(e) = result {
+		let _ = fs::remove_file(archive);
+		return Err(e);
+	}
+
+	if delete_original {
+		remove_path(source)?;
+	}
+
+	Ok(archive.metadata()?.len())
+}
+
+// compresses the beginnings of the files and extrapolates the ratio
+fn estimate(files: &[PathBuf], total: u64) -> io::Result<u64> {
+	let mut sample = Vec::new();
+	let mut buffer = vec![0; SAMPLE_BYTES_PER_FILE];
+
+	for path in files {
+		if sample.len() >= SAMPLE_BYTES {
+			break;
+		}
+		let Ok(mut file) = File::open(path) else {
+			continue;
+		};
+		let read = file.read(&mut buffer)?;
+		sample.extend_from_slice(&buffer[..read]);
+	}
+
+	if sample.is_empty() {
+		return Ok(0);
+	}
+
+	let compressed = zstd::bulk::compress(&sample, LEVEL)?;
+	Ok((total as f64 * compressed.len() as f64 / sample.len() as f64) as u64)
+}
+
+// returns the length and hash of every archived file, by path inside the archive
+fn write_archive(source: &Path, archive: &Path, sender: &mut Sender<Message>) -> io::Result<HashMap<PathBuf, (u64, u64)>> {
+	let encoder = zstd::Encoder::new(BufWriter::new(File::create(archive)?), LEVEL)?;
+	let mut builder = tar::Builder::new(encoder);
+	builder.follow_symlinks(false);
+
+	let name = PathBuf::from(source.file_name().unwrap_or_default());
+	let mut hashes = HashMap::new();
+	let mut archived = 0;
+	append_recursive(&mut builder, source, &name, &mut hashes, &mut archived, sender)?;
+
+	let encoder = builder.into_inner()?;
+	let writer = encoder.finish()?;
+	writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+	Ok(hashes)
+}
+
+fn append_recursive<W: io::Write>(builder: &mut tar::Builder<W>, path: &Path, name: &Path, hashes: &mut HashMap<PathBuf, (u64, u64)>, archived: &mut u64, sender: &mut Sender<Message>) -> io::Result<()> {
+	let metadata = path.symlink_metadata()?;
+
+	if metadata.is_dir() {
+		builder.append_dir(name, path)?;
+
+		let mut entries: Vec<_> = fs::read_dir(path)?.collect::<io::Result<_>>()?;
+		entries.sort_by_key(|e| e.file_name());
+		for entry in entries {
+			append_recursive(builder, &entry.path(), &name.join(entry.file_name()), hashes, archived, sender)?;
+		}
+	}
+	else if metadata.is_file() {
+		let mut header = tar::Header::new_gnu();
+		header.set_metadata(&metadata);
+
+		let mut reader = HashingReader { inner: File::open(path)?, hasher: DefaultHasher::new(), length: 0 };
+		builder.append_data(&mut header, name, &mut reader)?;
+		hashes.insert(name.to_owned(), (reader.length, reader.hasher.finish()));
+
+		*archived += reader.length;
+		sender.send(Message::Progress(*archived)).unwrap();
+	}
+	else {
+		// symlinks and special files are stored as they are
+		builder.append_path_with_name(path, name)?;
+	}
+
+	Ok(())
+}
+
+// reads the archive back and compares every file with what was written
+fn verify(archive: &Path, hashes: &HashMap<PathBuf, (u64, u64)>) -> io::Result<()> {
+	let mismatch = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("archive verification failed: {what}"));
+
+	let mut tar = tar::Archive::new(zstd::Decoder::new(File::open(archive)?)?);
+	let mut verified = 0;
+
+	for entry in tar.entries()? {
+		let entry = entry?;
+		if entry.header().entry_type() != tar::EntryType::Regular {
+			continue;
+		}
+
+		let path = entry.path()?.into_owned();
+		let mut reader = HashingReader { inner: entry, hasher: DefaultHasher::new(), length: 0 };
+		io::copy(&mut reader, &mut io::sink())?;
+
+		if hashes.get(&path) != Some(&(reader.length, reader.hasher.finish())) {
+			return Err(mismatch(&path.to_string_lossy()));
+		}
+		verified += 1;
+	}
+
+	if verified != hashes.len() {
+		return Err(mismatch("missing files"));
+	}
+
+	Ok(())
+}
+
+fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+	let metadata = path.symlink_metadata()?;
+	if metadata.is_dir() {
+		for entry in fs::read_dir(path)? {
+			collect_files(&entry?.path(), files)?;
+		}
+	}
+	else if metadata.is_file() {
+		files.push(path.to_owned());
+	}
+	Ok(())
+}
+
+struct HashingReader<R: Read> {
+	inner: R,
+	hasher: DefaultHasher,
+	length: u64,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let read = self.inner.read(buf)?;
+		self.hasher.write(&buf[..read]);
+		self.length += read as u64;
+		Ok(read)
+	}
+}
\ No newline at end of file
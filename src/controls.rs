@@ -3,12 +3,52 @@ use egui_macroquad::macroquad;
 use macroquad::prelude::*;
 use crate::helper::*;
 
+const DOUBLE_CLICK_TIME: f64 = 0.4;
+
+// an input that can be bound to a tree action
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Gesture {
+	LeftClick,
+	DoubleClick,
+	MiddleClick,
+	Key(KeyCode),
+}
+
+impl Gesture {
+	pub const ALL: [Gesture; 6] = [
+		Gesture::LeftClick,
+		Gesture::DoubleClick,
+		Gesture::MiddleClick,
+		Gesture::Key(KeyCode::Enter),
+		Gesture::Key(KeyCode::Backspace),
+		Gesture::Key(KeyCode::Space),
+	];
+
+	pub fn name(&self) -> String {
+		match self {
+			Gesture::LeftClick => "left click".into(),
+			Gesture::DoubleClick => "double click".into(),
+			Gesture::MiddleClick => "middle click".into(),
+			Gesture::Key(key) => format!("{key:?} key"),
+		}
+	}
+}
+
+pub struct Bindings {
+	pub expand: Gesture,
+	pub collapse: Gesture,
+}
+
 pub struct Controls {
 	target: Camera2D,
 	camera: Camera2D,
 	mouse_world: Vec2,
 	last_mouse_world: Vec2,
 	drag: Vec2,
+	pub bindings: Bindings,
+	last_left_click: f64,
+	double_clicked: bool,
+	dragged_since_mmb_down: bool, // middle drag zooms, only a still middle click is a gesture
 }
 
 impl Controls {
@@ -30,6 +70,13 @@ impl Controls {
 			mouse_world: vec2(0.0, 0.0),
 			last_mouse_world: vec2(0.0, 0.0),
 			drag: vec2(0.0, 0.0),
+			bindings: Bindings {
+				expand: Gesture::DoubleClick,
+				collapse: Gesture::MiddleClick,
+			},
+			last_left_click: f64::NEG_INFINITY,
+			double_clicked: false,
+			dragged_since_mmb_down: false,
 		}
 	}
 
@@ -50,7 +97,7 @@ impl Controls {
 		if d_zoom != 0.0 {
 			self.target.target = self.mouse_world;
 
-			self.target.zoom.x = self.target.zoom.x * 1.001f32.powf(d_zoom);
+			self.target.zoom.x *= 1.001f32.powf(d_zoom);
 			self.target.zoom.y = self.target.zoom.x * screen_width() / screen_height();
 
 			let mouse_world = self.target.screen_to_world(mouse_screen);
@@ -68,10 +115,42 @@ impl Controls {
 		self.camera.target = lerp(self.camera.target, self.target.target, 1.0 - 0.1f32.powf(10.0*get_frame_time()));
 		self.camera.zoom =   lerp(self.camera.zoom,   self.target.zoom,   1.0 - 0.1f32.powf(10.0*get_frame_time()));
 
+		self.double_clicked = false;
+		if is_mouse_button_pressed(MouseButton::Left) {
+			self.double_clicked = get_time() - self.last_left_click < DOUBLE_CLICK_TIME;
+			self.last_left_click = if self.double_clicked { f64::NEG_INFINITY } else { get_time() };
+		}
+
+		if is_mouse_button_pressed(MouseButton::Middle) {
+			self.dragged_since_mmb_down = false;
+		}
+		else if is_mouse_button_down(MouseButton::Middle) && mouse_delta_position() != vec2(0.0, 0.0) {
+			self.dragged_since_mmb_down = true;
+		}
+
 		self.drag = self.mouse_world - self.last_mouse_world;
         self.last_mouse_world = self.target.screen_to_world(mouse_screen);
 	}
 
+	// pointer gestures are ignored when the pointer is over the ui, keys when the ui has keyboard focus
+	pub fn triggered(&self, gesture: Gesture, pointer_free: bool, keyboard_free: bool) -> bool {
+		match gesture {
+			Gesture::LeftClick => pointer_free && is_mouse_button_pressed(MouseButton::Left),
+			Gesture::DoubleClick => pointer_free && self.double_clicked,
+			Gesture::MiddleClick => pointer_free && is_mouse_button_released(MouseButton::Middle) && !self.dragged_since_mmb_down,
+			Gesture::Key(key) => keyboard_free && is_key_pressed(key),
+		}
+	}
+
+	// moves the camera so that the rect fills the screen
+	pub fn zoom_to(&mut self, rect: Rect) {
+		let aspect = screen_width() / screen_height();
+		let zoom = (2.0 / rect.w).min(2.0 / (rect.h * aspect)) * 0.9;
+
+		self.target.target = rect.center();
+		self.target.zoom = vec2(zoom, zoom * aspect);
+	}
+
 	pub fn camera(&self) -> &Camera2D {
 		&self.camera
 	}
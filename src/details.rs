@@ -0,0 +1,158 @@
+use std::path::Path;
+
+use egui_macroquad::egui::{Ui, Grid};
+
+use fsexpl_core::{metadata::Metadata, text::{bytes_to_text, time_to_text}, TreeNode};
+
+use crate::node::Node;
+
+// everything the details panel shows about the selected node, computed once per selection
+pub struct Details {
+	path: String,
+	bytes: u64,
+	of_parent: Option<f64>, // None for the root
+	of_root: f64,
+	files: u64,
+	dirs: u64,
+	deepest: Option<(String, u64)>, // path, depth below the selected node
+	newest: Option<(String, i64)>, // path, modification time
+	oldest: Option<(String, i64)>,
+	metadata: Metadata,
+	times: Option<(i64, i64)>, // accessed, changed; read from disk as nodes don't keep them, None for trees from elsewhere
+	on_disk: bool, // the path exists on the local disk, so the actions on files can be offered
+}
+
+impl Details {
+	// local says the tree was scanned from this disk, otherwise a file at the same path is a different one
+	pub fn new(root: &Node, path: &str, local: bool) -> Option<Self> {
+		let node = root.find(path)?;
+
+		let parent_path = node.path_prefix().trim_end_matches(['/', '\\']);
+		let of_parent = if std::ptr::eq(node, root) {
+			None
+		}
+		else {
+			root.find(parent_path).map(|parent| percentage(node.bytes(), parent.bytes()))
+		};
+
+		let on_disk = local && Path::new(path).symlink_metadata().is_ok();
+		let mut details = Self {
+			path: node.get_full_path(),
+			bytes: node.bytes(),
+			of_parent,
+			of_root: percentage(node.bytes(), root.bytes()),
+			files: node.files(),
+			dirs: node.dirs(),
+			deepest: deepest(node),
+			newest: None,
+			oldest: None,
+			metadata: *node.metadata(),
+			times: if on_disk { access_change_times(&node.get_full_path()) } else { None },
+			on_disk,
+		};
+
+		for child in node.children() {
+			details.find_newest_oldest(child);
+		}
+
+		Some(details)
+	}
+
+	fn find_newest_oldest(&mut self, node: &Node) {
+		if node.is_dir() {
+			for child in node.children() {
+				self.find_newest_oldest(child);
+			}
+		}
+		else {
+			let modified = node.metadata().modified;
+			if self.newest.as_ref().is_none_or(|(_, t)| modified > *t) {
+				self.newest = Some((node.get_full_path(), modified));
+			}
+			if self.oldest.as_ref().is_none_or(|(_, t)| modified < *t) {
+				self.oldest = Some((node.get_full_path(), modified));
+			}
+		}
+	}
+
+	pub fn path(&self) -> &str {
+		&self.path
+	}
+
+	pub fn on_disk(&self) -> bool {
+		self.on_disk
+	}
+
+	pub fn draw(&self, ui: &mut Ui) {
+		ui.label(&self.path);
+
+		Grid::new("details").num_columns(2).show(ui, |ui| {
+			let mut row = |name: &str, value: String| {
+				ui.label(name);
+				ui.label(value);
+				ui.end_row();
+			};
+
+			row("size", bytes_to_text(self.bytes));
+			row("allocated", bytes_to_text(self.metadata.blocks * 512));
+			if let Some(class) = self.metadata.storage_class {
+				row("storage class", class.name().to_string());
+			}
+			if let Some(of_parent) = self.of_parent {
+				row("of parent", format!("{:.1}%", of_parent));
+			}
+			row("of root", format!("{:.1}%", self.of_root));
+			row("items", (self.files + self.dirs).to_string());
+			row("files", self.files.to_string());
+			row("subdirectories", self.dirs.to_string());
+			if let Some((path, depth)) = &self.deepest {
+				row("deepest", format!("{path} ({depth} levels)"));
+			}
+			if let Some((path, time)) = &self.newest {
+				row("newest", format!("{path} ({})", time_to_text(*time)));
+			}
+			if let Some((path, time)) = &self.oldest {
+				row("oldest", format!("{path} ({})", time_to_text(*time)));
+			}
+			row("mode", self.metadata.mode_text());
+			row("owner", format!("uid {} gid {}", self.metadata.uid, self.metadata.gid));
+			row("modified", time_to_text(self.metadata.modified));
+			if let Some((accessed, changed)) = self.times {
+				row("accessed", time_to_text(accessed));
+				row("changed", time_to_text(changed));
+			}
+			row("inode", format!("{} (device {})", self.metadata.inode, self.metadata.device));
+		});
+	}
+}
+
+// None for paths that aren't on the disk, like members of archives
+#[cfg(unix)]
+fn access_change_times(path: &str) -> Option<(i64, i64)> {
+	use std::os::unix::fs::MetadataExt;
+	let metadata = std::fs::symlink_metadata(path).ok()?;
+	Some((metadata.atime(), metadata.ctime()))
+}
+
+#[cfg(not(unix))]
+fn access_change_times(path: &str) -> Option<(i64, i64)> {
+	use std::time::UNIX_EPOCH;
+	let metadata = std::fs::symlink_metadata(path).ok()?;
+	let accessed = metadata.accessed().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+	let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+	Some((accessed, modified))
+}
+
+// follows the children holding the maximum depth
+fn deepest(node: &Node) -> Option<(String, u64)> {
+	let mut current = node;
+	while let Some(child) = current.children().iter().find(|c| c.depth() + 1 == current.depth()) {
+		current = child;
+	}
+
+	if std::ptr::eq(current, node) { None } else { Some((current.get_full_path(), node.depth())) }
+}
+
+fn percentage(part: u64, whole: u64) -> f64 {
+	if whole == 0 { 100.0 } else { part as f64 / whole as f64 * 100.0 }
+}
\ No newline at end of file
@@ -0,0 +1,250 @@
+use std::{thread, sync::mpsc::{channel, Receiver, Sender}, path::{Path, PathBuf}, collections::{HashMap, HashSet, hash_map::DefaultHasher}, fs::{File, Metadata}, io::{self, Read}, hash::Hasher, cmp::Reverse};
+
+const PARTIAL_HASH_BYTES: usize = 4096;
+const READ_BUFFER_BYTES: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub enum Message {
+	Denied(PathBuf),
+	Progress(u64), // bytes hashed
+	Finished(Vec<DuplicateGroup>),
+}
+
+#[derive(Debug)]
+pub struct DuplicateGroup {
+	pub bytes: u64, // size of a single copy
+	pub paths: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+	pub fn wasted(&self) -> u64 {
+		self.bytes * (self.paths.len() as u64 - 1)
+	}
+}
+
+pub enum Duplicates {
+	Empty,
+	Processing(u64, Receiver<Message>), // bytes hashed, message receiver
+	Ready(Vec<DuplicateGroup>, u64), // groups sorted by wasted space, total wasted bytes
+}
+
+impl Duplicates {
+	// the paths of the files in a ready tree
+	pub fn scan(&mut self, files: Vec<PathBuf>) {
+		if self.is_processing() {
+			return;
+		}
+
+		let (mut sender, receiver) = channel::<Message>();
+
+		thread::spawn(move || {
+			let groups = find_duplicates(files, &mut sender);
+
+			sender.send(Message::Finished(groups)).unwrap();
+		});
+
+		*self = Self::Processing(0, receiver);
+	}
+
+	// returns true if the search finished
+	pub fn process(&mut self, denied: &mut Vec<PathBuf>) -> bool {
+		let mut finished_groups = None;
+
+		if let Duplicates::Processing(bytes, receiver) = self {
+			while let Ok(m) = receiver.try_recv() {
+				match m {
+					Message::Progress(new_bytes) => *bytes = new_bytes,
+					Message::Denied(path) => denied.push(path),
+					Message::Finished(groups) => finished_groups = Some(groups),
+				}
+			}
+		}
+
+		if let Some(groups) = finished_groups {
+			let wasted = groups.iter().map(|g| g.wasted()).sum();
+			*self = Duplicates::Ready(groups, wasted);
+			return true;
+		}
+
+		false
+	}
+
+	pub fn is_processing(&self) -> bool {
+		matches!(self, Self::Processing(_, _))
+	}
+}
+
+fn find_duplicates(files: Vec<PathBuf>, sender: &mut Sender<Message>) -> Vec<DuplicateGroup> {
+	let mut hashed = 0;
+
+	// grouped by the length on the disk, the size in the tree may be rounded to blocks
+	// paths that aren't files there, like archive members or files removed since the scan, are left out
+	// hardlinks to the same file take the space only once, so only the first path of each is kept
+	// empty files are all identical, but there is nothing to reclaim
+	let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+	let mut seen = HashSet::new();
+	for path in files {
+		let Ok(metadata) = path.symlink_metadata() else { continue };
+		if metadata.is_file() && metadata.len() > 0 && file_id(&metadata).is_none_or(|id| seen.insert(id)) {
+			by_size.entry(metadata.len()).or_default().push(path);
+		}
+	}
+
+	let mut groups = Vec::new();
+
+	for (bytes, paths) in by_size {
+		if paths.len() < 2 {
+			continue;
+		}
+
+		// the hash only narrows the candidates down, they are compared byte for byte before being reported
+		let by_partial = group_by_hash(paths, Some(PARTIAL_HASH_BYTES), sender, &mut hashed);
+
+		for candidates in by_partial {
+			for paths in group_by_contents(candidates, sender, &mut hashed) {
+				groups.push(DuplicateGroup { bytes, paths });
+			}
+		}
+	}
+
+	groups.sort_unstable_by_key(|g| Reverse(g.wasted()));
+	groups
+}
+
+// returns only the groups with at least two members
+fn group_by_hash(paths: Vec<PathBuf>, limit: Option<usize>, sender: &mut Sender<Message>, hashed: &mut u64) -> Vec<Vec<PathBuf>> {
+	let mut by_hash: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+	for path in paths {
+		match hash_file(&path, limit, sender, hashed) {
+			Some(hash) => by_hash.entry(hash).or_default().push(path),
+			None => sender.send(Message::Denied(path)).unwrap(),
+		}
+	}
+
+	by_hash.into_values().filter(|g| g.len() > 1).collect()
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<(u64, u64)> {
+	use std::os::unix::fs::MetadataExt;
+	Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<(u64, u64)> {
+	None
+}
+
+// returns only the groups with at least two members
+fn group_by_contents(paths: Vec<PathBuf>, sender: &mut Sender<Message>, hashed: &mut u64) -> Vec<Vec<PathBuf>> {
+	let mut groups: Vec<Vec<PathBuf>> = Vec::new();
+
+	'paths: for path in paths {
+		for group in &mut groups {
+			match same_contents(&group[0], &path, sender, hashed) {
+				Ok(true) => {
+					group.push(path);
+					continue 'paths;
+				},
+				Ok(false) => {},
+				Err(_) => {
+					sender.send(Message::Denied(path)).unwrap();
+					continue 'paths;
+				},
+			}
+		}
+		groups.push(vec![path]);
+	}
+
+	groups.retain(|g| g.len() > 1);
+	groups
+}
+
+fn same_contents(first: &Path, second: &Path, sender: &mut Sender<Message>, hashed: &mut u64) -> io::Result<bool> {
+	let (mut first, mut second) = (File::open(first)?, File::open(second)?);
+	let mut first_buffer = vec![0; READ_BUFFER_BYTES];
+	let mut second_buffer = vec![0; READ_BUFFER_BYTES];
+
+	loop {
+		let read = fill(&mut first, &mut first_buffer)?;
+		if fill(&mut second, &mut second_buffer)? != read || first_buffer[..read] != second_buffer[..read] {
+			return Ok(false);
+		}
+		if read == 0 {
+			return Ok(true);
+		}
+
+		*hashed += read as u64;
+		sender.send(Message::Progress(*hashed)).unwrap();
+	}
+}
+
+// reads until the buffer is full or the file ends
+fn fill(file: &mut File, buffer: &mut [u8]) -> io::Result<usize> {
+	let mut filled = 0;
+	while filled < buffer.len() {
+		match file.read(&mut buffer[filled..])? {
+			0 => break,
+			read => filled += read,
+		}
+	}
+	Ok(filled)
+}
+
+fn hash_file(path: &Path, limit: Option<usize>, sender: &mut Sender<Message>, hashed: &mut u64) -> Option<u64> {
+	let mut file = File::open(path).ok()?;
+	let mut hasher = DefaultHasher::new();
+	let mut buffer = vec![0; limit.unwrap_or(READ_BUFFER_BYTES).min(READ_BUFFER_BYTES)];
+	let mut remaining = limit.unwrap_or(usize::MAX);
+
+	while remaining > 0 {
+		let to_read = buffer.len().min(remaining);
+		let read = file.read(&mut buffer[..to_read]).ok()?;
+		if read == 0 {
+			break;
+		}
+		hasher.write(&buffer[..read]);
+		remaining -= read;
+
+		let before = *hashed / READ_BUFFER_BYTES as u64;
+		*hashed += read as u64;
+		if *hashed / READ_BUFFER_BYTES as u64 != before {
+			sender.send(Message::Progress(*hashed)).unwrap();
+		}
+	}
+
+	Some(hasher.finish())
+}
+#[cfg(test)]
+mod tests {
+	use std::{env, fs, process};
+
+	use super::*;
+
+	#[test]
+	fn only_files_on_the_disk_are_compared() {
+		let dir = env::temp_dir().join(format!("fsexpl-duplicates-{}", process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("a"), [1; 5000]).unwrap();
+		fs::write(dir.join("b"), [1; 5000]).unwrap();
+		fs::write(dir.join("c"), [2; 5000]).unwrap();
+		#[cfg(unix)]
+		fs::hard_link(dir.join("a"), dir.join("link")).unwrap();
+
+		let (mut sender, receiver) = channel();
+		// a member of an archive, a file gone since the scan and a directory aren't read
+		let files = ["a", "b", "c", "link", "a/member", "gone", ""].map(|name| dir.join(name));
+		let groups = find_duplicates(files.to_vec(), &mut sender);
+		fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(groups.len(), 1);
+		assert_eq!(groups[0].bytes, 5000);
+		let mut paths = groups[0].paths.clone();
+		paths.sort();
+		assert_eq!(paths, [dir.join("a"), dir.join("b")]);
+		drop(sender);
+		assert!(receiver.iter().all(|m| !matches!(m, Message::Denied(_))));
+	}
+}
@@ -1,10 +1,11 @@
 use std::ops::{Add, Sub, Mul};
 use std::path::{Path, PathBuf};
-use std::fs;
+use std::{fs, io};
 
 use egui_macroquad::macroquad;
 use macroquad::prelude::*;
 use macroquad::rand::gen_range;
+use fsexpl_core::{layout, metadata::StorageClass};
 
 pub fn lerp<T, U>(a: T, b: T, t: U) -> T 
 where 
@@ -42,6 +43,21 @@ pub fn random_col(brightness: f32) -> Color {
 	col_from_hsv(gen_range(0.0, 1.0), gen_range(0.4, 1.0), brightness)
 }
 
+// green for frequent access, yellow for infrequent, blue to purple for the archive tiers
+pub fn storage_class_col(class: StorageClass) -> Color {
+	let hue = match class {
+		StorageClass::Standard | StorageClass::ExpressOnezone => 0.33,
+		StorageClass::ReducedRedundancy => 0.25,
+		StorageClass::IntelligentTiering => 0.45,
+		StorageClass::StandardIa | StorageClass::OnezoneIa => 0.15,
+		StorageClass::GlacierIr => 0.55,
+		StorageClass::Glacier => 0.65,
+		StorageClass::DeepArchive => 0.78,
+		StorageClass::Other => 0.0,
+	};
+	col_from_hsv(hue, 0.7, 0.6)
+}
+
 pub fn _dir_size(path: &Path) -> (u64, Vec<PathBuf>) {
 	
 	fn dir_size(path: &Path, denied: &mut Vec<PathBuf>) -> u64 {
@@ -87,6 +103,16 @@ pub fn _dir_size(path: &Path) -> (u64, Vec<PathBuf>) {
     (dir_size(Path::new(path), &mut denied), denied)
 }
 
+// removes a file, symlink or a whole directory tree
+pub fn remove_path(path: &Path) -> io::Result<()> {
+	if path.symlink_metadata()?.is_dir() {
+		fs::remove_dir_all(path)
+	}
+	else {
+		fs::remove_file(path)
+	}
+}
+
 pub fn draw_centered_text(text: &str, size: f32, pos: Vec2) {
 	let text_size = measure_text(text, None, 16, 1.0/16.0*size);
 	draw_text_ex(
@@ -104,15 +130,6 @@ pub fn draw_centered_text(text: &str, size: f32, pos: Vec2) {
 	);
 }
 
-pub fn bytes_to_text(bytes: u64) -> String {
-	let units = ["", "K", "M", "G", "T", "P", "E"];
-	let order = if bytes == 0 { 0 } else { ((bytes as f32).log2() * 0.1) as usize };
-	let order = order.min(units.len() - 1);
-	let display_number = bytes as f32 / 1024f32.powf(order as f32);
-	let dn_digits = if display_number == 0.0 { 1 } else { display_number.log10() as usize + 1 };
-	return format!("{:.2$}{}B", display_number, units[order], (3 - dn_digits.min(3)));
-}
-
 pub fn shrink_rect(rect: &mut Rect, amount: f32) {
 	rect.x += amount;
 	rect.y += amount;
@@ -122,4 +139,12 @@ pub fn shrink_rect(rect: &mut Rect, amount: f32) {
 
 pub fn shrink_rect_margin(rect: &mut Rect, percentage: f32) {
 	shrink_rect(rect, percentage * rect.w.min(rect.h));
-}
\ No newline at end of file
+}
+
+pub fn to_layout_rect(rect: Rect) -> layout::Rect {
+	layout::Rect::new(rect.x, rect.y, rect.w, rect.h)
+}
+
+pub fn from_layout_rect(rect: layout::Rect) -> Rect {
+	Rect::new(rect.x, rect.y, rect.w, rect.h)
+}
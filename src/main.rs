@@ -4,21 +4,54 @@ mod node;
 mod tree;
 mod state;
 mod icon;
+mod duplicates;
+mod details;
+mod trash;
+mod basket;
+mod script;
+mod open;
+mod relocate;
+mod picker;
+mod compress;
+mod args;
+mod report;
+mod render;
 
 use egui_macroquad::macroquad::{self, prelude::*};
 use state::State;
+use args::{Args, Mode, default_path};
 
-#[macroquad::main("Filesystem Visualiser")]
-async fn main() {
+fn main() {
 	std::env::set_var("RUST_BACKTRACE", "1");
 
-	let mut state = State::new(); 
+	// parsed before the window opens so --help and bad arguments never flash one
+	let args = Args::from_env();
 
-	loop {
-		state.frame();
-
-		next_frame().await;
+	match &args.mode {
+		Mode::Gui => {},
+		Mode::Report { top, depth } => {
+			let path = args.path.unwrap_or_else(default_path);
+			std::process::exit(report::run(&path, &args.options, *top, *depth));
+		},
+		Mode::Export { format, out, options } => {
+			let path = args.path.unwrap_or_else(default_path);
+			std::process::exit(report::export(&path, &args.options, *format, out.as_deref(), options));
+		},
+		Mode::Render { out, filter, width, height } => {
+			let path = args.path.unwrap_or_else(default_path);
+			std::process::exit(report::render(&path, &args.options, out, filter, *width, *height));
+		},
 	}
+
+	macroquad::Window::new("Filesystem Visualiser", async move {
+		let mut state = State::new(args);
+
+		loop {
+			state.frame();
+
+			next_frame().await;
+		}
+	});
 }
 
 /*
@@ -1,112 +1,73 @@
-use std::{fs, path::Path, sync::mpsc::Sender, fmt::Debug};
+use std::{path::{Path, PathBuf}, fmt::Debug, collections::HashSet, ops::Deref};
 
 use egui_macroquad::macroquad;
 use macroquad::prelude::*;
+use fsexpl_core::{node::{self as model, Entry, TreeNode, Weight}, layout, text::bytes_to_text};
 
-use crate::{helper::{random_col, bytes_to_text, shrink_rect_margin}, tree::Message};
+use crate::{helper::{random_col, storage_class_col, shrink_rect_margin, to_layout_rect, from_layout_rect}, basket::Basket};
 
+// a scanned entry with the state of its tile, derefs to the entry for the model side
 pub struct Node {
-	path_prefix: String,
-	name: String,
-	bytes: u64,
+	entry: Entry,
 	children: Vec<Node>,
 	big_rect: Rect,
 	small_rect: Rect,
 	color: Color,
 	hovered: bool,
 	is_leaf: bool,
+	duplicate: bool, // a duplicated file, or a directory containing one
+	selected: bool,
+	marked: bool, // in the cleanup basket
 }
 
-impl Node {
-	pub fn new(path: &Path, rect: Rect, sender: &mut Sender<Message>, bytes_cummulative: &mut u64, counter: &mut u64) -> Option<Self> {
-
-		let mut small_rect = rect.clone();
+impl From<model::Node> for Node {
+	fn from(node: model::Node) -> Self {
+		let rect = Rect::new(0.0, 0.0, 1.0, 1.0);
+		let mut small_rect = rect;
 		shrink_rect_margin(&mut small_rect, 0.05);
 
-		let mut pre_path = path.to_string_lossy().to_string();
-		let mut name = None;
-
-		if pre_path.ends_with('/') {
-			pre_path.pop();
-		}
-		
-		let mut last_slash = None;
-		for (i, ch) in pre_path.bytes().enumerate() {
-			if ch == b'/' || ch == b'\\' {
-				last_slash = Some(i);
-			}
-		}
-		if let Some(last) = last_slash {
-			name = Some(pre_path.split_off(last + 1));
-		}
-		
-		let mut children = Vec::new();
-		let mut bytes = 0;
-
-		if path.is_file() {
-			let metadata = match path.metadata() {
-				Ok(m) => m,
-				Err(_) => {
-					sender.send(Message::Denied(path.to_owned())).unwrap();
-					return None;
-				}
-			};
-			bytes = metadata.len();
-			*bytes_cummulative += bytes;
-			*counter += 1;
-			if *counter % 100 == 0 {
-				sender.send(Message::Progress(*bytes_cummulative)).unwrap();
-			}
-		}
-		else {
-			let iterator = match fs::read_dir(path) {
-				Ok(i) => i,
-				Err(_) => {
-					sender.send(Message::Denied(path.to_owned())).unwrap();
-					return None;
-				},
-			};
-	
-			for entry in iterator {
-				let entry = match entry {
-					Ok(e) => e,
-					Err(_) => {
-						sender.send(Message::Denied(path.to_owned())).unwrap();
-						continue;
-					}
-				};
-
-				let child = Node::new(&entry.path(), Rect::new(1.0, 1.0, 1.0, 1.0), sender, bytes_cummulative, counter);
-				if let Some(child) = child {
-					bytes += child.bytes();
-					children.push(child);
-				}
-			}
-	
-			children.sort_unstable_by(|n1, n2| {n1.bytes.cmp(&n2.bytes)});
+		Self {
+			color: random_col(if node.entry.is_dir() {1.0} else {0.15}),
+			entry: node.entry,
+			children: node.children.into_iter().map(Node::from).collect(),
+			big_rect: rect,
+			small_rect,
+			hovered: false,
+			is_leaf: true,
+			duplicate: false,
+			selected: false,
+			marked: false,
 		}
+	}
+}
 
-		Some(
-			Self {
-				name: name.unwrap_or_else(|| String::from("-")),
-				path_prefix: pre_path,
-				bytes: bytes,
-				children,
-				big_rect: rect,
-				small_rect, 
-				color: random_col(if path.is_dir() {1.0} else {0.15}),
-				hovered: false,
-				is_leaf: true,
-			}
-		)
+impl Deref for Node {
+	type Target = Entry;
+
+	fn deref(&self) -> &Entry {
+		&self.entry
+	}
+}
+
+impl TreeNode for Node {
+	fn entry(&self) -> &Entry {
+		&self.entry
 	}
 
-	pub fn get_full_path(&self) -> String {
-		let mut path = self.path_prefix.clone();
-		path.push_str(&self.name);
-		return path;
+	fn entry_mut(&mut self) -> &mut Entry {
+		&mut self.entry
 	}
 
+	fn children(&self) -> &[Self] {
+		&self.children
+	}
+
+	fn children_mut(&mut self) -> &mut Vec<Self> {
+		&mut self.children
+	}
+}
+
+impl Node {
 	pub fn draw(&self) {
 		if self.is_leaf {
 			
@@ -116,8 +77,9 @@ impl Node {
 
 			draw_rectangle(self.big_rect.x, self.big_rect.y, self.big_rect.w, self.big_rect.h, self.color);
 
-			let upper_text_dim = measure_text(&self.name, None, 16, 1.0);
-			let lower_text_dim = measure_text(&bytes_to_text(self.bytes), None, 16, 1.0);
+			let upper_text_dim = measure_text(self.name(), None, 16, 1.0);
+			let lower_text = self.size_text();
+			let lower_text_dim = measure_text(&lower_text, None, 16, 1.0);
 
 			let upper_text_size = vec2(upper_text_dim.width, upper_text_dim.height);
 			let lower_text_size = vec2(lower_text_dim.width, lower_text_dim.height);
@@ -128,7 +90,7 @@ impl Node {
 			let scale = upper_text_max_scale.min(lower_text_max_scale);
 
 			draw_text_ex(
-				&self.name, 
+				self.name(), 
 				self.big_rect.center().x - upper_text_dim.width * 0.5 * scale, 
 				self.big_rect.center().y - margin - (upper_text_dim.height - upper_text_dim.offset_y)*scale, 
 				TextParams { 
@@ -143,7 +105,7 @@ impl Node {
 
 			if self.hovered {
 				draw_text_ex(
-					&bytes_to_text(self.bytes), 
+					&lower_text, 
 					self.big_rect.center().x - lower_text_dim.width * 0.5 * scale, 
 					self.big_rect.center().y + margin + (lower_text_dim.offset_y)*scale, 
 					TextParams { 
@@ -156,9 +118,14 @@ impl Node {
 					},
 				);
 			}
+
+			if self.duplicate {
+				let thickness = self.big_rect.w.min(self.big_rect.h) * 0.05;
+				draw_rectangle_lines(self.big_rect.x, self.big_rect.y, self.big_rect.w, self.big_rect.h, thickness, RED);
+			}
 		}
 		else {
-			let mut color = self.color.clone();
+			let mut color = self.color;
 			color.r *= 0.7;
 			color.g *= 0.7;
 			color.b *= 0.7;
@@ -168,133 +135,192 @@ impl Node {
 				child.draw();
 			}
 		}
+
+		if self.marked {
+			let thickness = self.big_rect.w.min(self.big_rect.h) * 0.03;
+			draw_rectangle(self.big_rect.x, self.big_rect.y, self.big_rect.w, self.big_rect.h, Color::new(0.0, 0.0, 0.0, 0.5));
+			draw_rectangle_lines(self.big_rect.x, self.big_rect.y, self.big_rect.w, self.big_rect.h, thickness, YELLOW);
+		}
+
+		if self.selected {
+			let thickness = self.big_rect.w.min(self.big_rect.h) * 0.03;
+			draw_rectangle_lines(self.big_rect.x, self.big_rect.y, self.big_rect.w, self.big_rect.h, thickness, WHITE);
+		}
 	}
 
-	// (tooltip, collapse parent)
-	pub fn handle_mouse(&mut self, pos: Vec2, clicked_l: bool, clicked_r: bool) -> (Option<String>, bool) {
+	// (tooltip, path of the tile under the cursor)
+	pub fn handle_mouse(&mut self, pos: Vec2) -> (Option<String>, Option<String>) {
 		let mut tooltip = None;
+		let mut hovered = None;
 
 		if self.is_leaf {
 			self.hovered = self.big_rect.contains(pos);
 			if self.hovered {
-				tooltip = Some(self.get_full_path());
-			}
-
-			if self.hovered && clicked_l && self.children.len() != 0 {
-				self.is_leaf = false;				
-				Self::place_children(&mut self.children, self.small_rect);
-			}
-			else if self.hovered && clicked_r {
-				return (tooltip, true);
+				tooltip = Some(self.describe());
+				hovered = Some(self.get_full_path());
 			}
 		}
 		else {
 			self.hovered = false;
 
-			let mut should_collapse = false;
 			for child in &mut self.children {
-				let resp = child.handle_mouse(pos, clicked_l, clicked_r);
-				should_collapse |= resp.1;
+				let resp = child.handle_mouse(pos);
 				if resp.0.is_some() {
 					tooltip = resp.0;
 				}
-			}
-			if should_collapse {
-				self.collapse_recursive();
+				if resp.1.is_some() {
+					hovered = resp.1;
+				}
 			}
 
+			// the frame around the children belongs to this directory
 			if self.big_rect.contains(pos) && !self.small_rect.contains(pos) {
-				tooltip = Some(self.get_full_path())
+				tooltip = Some(self.describe());
+				hovered = Some(self.get_full_path());
 			}
 		}		
 
-		(tooltip, false)
+		(tooltip, hovered)
 	}
 
-	pub fn collapse_recursive(&mut self) {
-		self.is_leaf = true;
+	// splits the directory with the given path into its children, returns false if there is nothing to split
+	pub fn expand(&mut self, path: &str, weight: Weight) -> bool {
+		match self.find_mut(path) {
+			Some(node) if node.is_leaf && !node.children.is_empty() => {
+				node.is_leaf = false;
+				node.layout_children(weight);
+				true
+			},
+			_ => false,
+		}
+	}
+
+	pub fn collapse(&mut self, path: &str) {
+		if let Some(node) = self.find_mut(path) {
+			node.collapse_recursive();
+		}
+	}
+
+	// marks the node with the given path as selected and clears all others
+	pub fn select(&mut self, path: Option<&str>) {
+		self.selected = path.is_some_and(|p| p == self.get_full_path());
 
 		for child in &mut self.children {
-			child.collapse_recursive();
+			child.select(path);
 		}
 	}
 
-	fn place_children(slice: &mut [Node], rect: Rect) {
-		// end condition - give all space if only one node is left
-		if slice.len() == 1 {
-			slice[0].big_rect = rect;
-			slice[0].small_rect = rect.clone();
-			shrink_rect_margin(&mut slice[0].small_rect, 0.05);
-			return;
+	// marks the nodes in the basket and clears all others
+	pub fn mark_basket(&mut self, basket: &Basket) {
+		self.marked = basket.contains(&self.get_full_path());
+
+		for child in &mut self.children {
+			child.mark_basket(basket);
 		}
+	}
+
+	// detaches the descendant with the given path, updating the sizes and layout of its ancestors
+	pub fn remove(&mut self, path: &str, weight: Weight) -> Option<Node> {
+		let removed = TreeNode::remove(self, path)?;
+		self.relayout(weight);
+		Some(removed)
+	}
+
+	// attaches a node collapsed under its parent directory, gives it back if the parent isn't in this subtree
+	pub fn insert(&mut self, mut node: Node, weight: Weight) -> Result<(), Box<Node>> {
+		node.collapse_recursive();
+		TreeNode::insert(self, node)?;
+		self.relayout(weight);
+		Ok(())
+	}
 
-		let mut size_sum = 0;
+	pub fn collapse_recursive(&mut self) {
+		self.is_leaf = true;
 
-		for node in slice.iter() {
-			size_sum += node.bytes;
+		for child in &mut self.children {
+			child.collapse_recursive();
 		}
+	}
 
-		let mut half_sum = 0;
-		let mut split_index = 0;
+	fn layout_children(&mut self, weight: Weight) {
+		self.children.sort_unstable_by_key(|n| n.weight(weight));
+		Self::place_children(&mut self.children, self.small_rect, weight);
+	}
 
-		// find the index in slice, where [0, split_index) and [split_index, length) are as evenly split as possible
-		for node in slice.iter() {
-			half_sum += node.bytes;
-			split_index += 1;
-			if half_sum > size_sum/2 {
-				break;
-			}
+	// lays out every expanded directory again, used when the weight or the tree changes
+	pub fn relayout(&mut self, weight: Weight) {
+		if self.children.is_empty() {
+			self.is_leaf = true;
 		}
+		if !self.is_leaf {
+			self.layout_children(weight);
 
-		// correct the split_index if overshoot
-		if ((size_sum/2) as i128 - half_sum as i128).abs() >=
-		   ((size_sum/2) as i128 - (half_sum as i128 - slice[split_index - 1].bytes as i128)).abs() {
-			split_index -= 1;
-			half_sum -= slice[split_index].bytes;
+			for child in &mut self.children {
+				child.relayout(weight);
+			}
 		}
+	}
 
-		// shouldn't ever happen, but if so this prevents infinite recursion
-		if split_index == 0 {
-			split_index = 1;
-			half_sum = slice[0].bytes;
+	fn place_children(slice: &mut [Node], rect: Rect, weight: Weight) {
+		let weights: Vec<u64> = slice.iter().map(|n| n.weight(weight)).collect();
+
+		for (node, tile) in slice.iter_mut().zip(layout::split(&weights, to_layout_rect(rect))) {
+			node.big_rect = from_layout_rect(tile);
+			node.small_rect = from_layout_rect(tile.inner());
 		}
-		if split_index == slice.len() {
-			split_index = slice.len() - 1;
-			half_sum = size_sum - slice[slice.len() - 1].bytes;
+	}
+
+	// colors bucket objects by their storage class, or randomly like other files again
+	pub fn color_storage_classes(&mut self, enabled: bool) {
+		if let Some(class) = self.metadata().storage_class {
+			self.color = if enabled { storage_class_col(class) } else { random_col(0.15) };
 		}
 
-		// split the rectangle
-		let proportion = half_sum as f32 / size_sum as f32;
-		let mut rect1 = rect.clone();
-		let mut rect2 = rect.clone();
+		for child in &mut self.children {
+			child.color_storage_classes(enabled);
+		}
+	}
 
-		if rect.w > rect.h || rect.w == rect.h && rand::gen_range(0, 2) == 0 {
-			rect1.w *= proportion;
-			rect2.w *= 1.0 - proportion;
-			rect2.x += rect1.w;
+	// returns true if the subtree contains any of the paths
+	pub fn mark_duplicates(&mut self, paths: &HashSet<PathBuf>) -> bool {
+		if self.is_dir() {
+			self.duplicate = false;
+			for child in &mut self.children {
+				self.duplicate |= child.mark_duplicates(paths);
+			}
 		}
 		else {
-			rect1.h *= proportion;
-			rect2.h *= 1.0 - proportion;
-			rect2.y += rect1.h;
+			self.duplicate = paths.contains(Path::new(&self.get_full_path()));
 		}
-		
-		// divide further
-		Self::place_children(&mut slice[..split_index], rect1);
-		Self::place_children(&mut slice[split_index..], rect2);
+
+		self.duplicate
 	}
 
-	pub fn bytes(&self) -> u64 {
-		self.bytes
+	// bytes, with the recursive item count for directories
+	fn size_text(&self) -> String {
+		if self.is_dir() {
+			format!("{} | {} items", bytes_to_text(self.bytes()), self.files() + self.dirs())
+		}
+		else {
+			bytes_to_text(self.bytes())
+		}
 	}
 
 	pub fn color(&self) -> Color {
 		self.color
 	}
+
+	pub fn is_expanded(&self) -> bool {
+		!self.is_leaf
+	}
+
+	pub fn big_rect(&self) -> Rect {
+		self.big_rect
+	}
 }
 
 impl Debug for Node {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		return f.write_str("node");
+		f.write_str("node")
 	}
-}
\ No newline at end of file
+}
@@ -0,0 +1,44 @@
+use std::{process::Command, path::Path, io, thread};
+
+// command templates, split on whitespace, with {path} replaced by the full path and {parent} by the containing folder
+// they run inside the directory itself, or the containing folder for files
+pub struct Commands {
+	pub folder: String,
+	pub default_app: String,
+	pub terminal: String,
+}
+
+impl Commands {
+	pub fn new() -> Self {
+		Self {
+			folder: "xdg-open {parent}".into(),
+			default_app: "xdg-open {path}".into(),
+			terminal: "x-terminal-emulator".into(),
+		}
+	}
+}
+
+pub fn run(template: &str, path: &str) -> io::Result<()> {
+	let path = Path::new(path);
+	let parent = match path.parent() {
+		Some(p) if !p.as_os_str().is_empty() => p,
+		_ => Path::new("."),
+	};
+	let working_dir = if path.is_dir() { path } else { parent };
+
+	let mut args = template.split_whitespace().map(|arg| {
+		arg.replace("{path}", &path.to_string_lossy())
+			.replace("{parent}", &parent.to_string_lossy())
+	});
+	let program = args.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+
+	let mut child = Command::new(program)
+		.args(args)
+		.current_dir(working_dir)
+		.spawn()?;
+
+	// reap the process when it exits
+	thread::spawn(move || child.wait());
+
+	Ok(())
+}
\ No newline at end of file
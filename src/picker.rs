@@ -0,0 +1,64 @@
+use std::{fs, path::Path};
+
+use egui_macroquad::egui::{Ui, TextEdit, ScrollArea};
+
+// a text field for a directory with its subdirectories listed below for navigation
+pub struct DirPicker {
+	pub dir: String,
+	listed: Option<String>, // the dir that subdirs were read from
+	subdirs: Vec<String>,
+}
+
+impl DirPicker {
+	pub fn new(dir: String) -> Self {
+		Self {
+			dir,
+			listed: None,
+			subdirs: Vec::new(),
+		}
+	}
+
+	pub fn draw(&mut self, ui: &mut Ui) {
+		ui.add(TextEdit::singleline(&mut self.dir));
+
+		if self.listed.as_ref() != Some(&self.dir) {
+			self.subdirs = list_subdirs(Path::new(&self.dir));
+			self.listed = Some(self.dir.clone());
+		}
+
+		let mut chosen = None;
+		ScrollArea::vertical()
+			.id_source("dir picker")
+			.max_height(200.0)
+			.show(ui, |ui| {
+				if let Some(parent) = Path::new(&self.dir).parent() {
+					if ui.selectable_label(false, "..").clicked() {
+						chosen = Some(parent.to_string_lossy().to_string());
+					}
+				}
+				for name in &self.subdirs {
+					if ui.selectable_label(false, name).clicked() {
+						chosen = Some(Path::new(&self.dir).join(name).to_string_lossy().to_string());
+					}
+				}
+			});
+
+		if let Some(dir) = chosen {
+			self.dir = dir;
+		}
+	}
+}
+
+fn list_subdirs(dir: &Path) -> Vec<String> {
+	let Ok(iterator) = fs::read_dir(dir) else {
+		return Vec::new();
+	};
+
+	let mut subdirs: Vec<String> = iterator
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.path().is_dir())
+		.map(|entry| entry.file_name().to_string_lossy().to_string())
+		.collect();
+	subdirs.sort();
+	subdirs
+}
\ No newline at end of file
@@ -0,0 +1,217 @@
+use std::{thread, sync::mpsc::{channel, Receiver, Sender}, path::{Path, PathBuf}, fs::{self, File}, io::{self, Read, Write}, collections::hash_map::DefaultHasher, hash::Hasher};
+
+use crate::helper::remove_path;
+
+const BUFFER_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug)]
+pub enum Message {
+	Progress(u64), // bytes copied
+	Finished(Result<(), String>),
+}
+
+pub enum Status {
+	Running(u64, Receiver<Message>), // bytes copied, message receiver
+	Done(Result<(), String>),
+}
+
+// a move of one file or directory into another directory, copying across devices
+pub struct Relocation {
+	pub source: PathBuf,
+	pub target: PathBuf, // destination directory joined with the source name
+	pub total: u64,
+	pub status: Status,
+}
+
+impl Relocation {
+	pub fn start(source: &Path, destination_dir: &Path, total: u64) -> Self {
+		let (mut sender, receiver) = channel::<Message>();
+
+		let target = destination_dir.join(source.file_name().unwrap_or_default());
+		let (source_buf, target_buf) = (source.to_owned(), target.clone());
+		thread::spawn(move || {
+			let result = relocate(&source_buf, &target_buf, &mut sender).map_err(|e| e.to_string());
+
+			sender.send(Message::Finished(result)).unwrap();
+		});
+
+		Self {
+			source: source.to_owned(),
+			target,
+			total,
+			status: Status::Running(0, receiver),
+		}
+	}
+
+	// returns true if the move finished, successfully or not
+	pub fn process(&mut self) -> bool {
+		let mut result = None;
+
+		if let Status::Running(bytes, receiver) = &mut self.status {
+			while let Ok(m) = receiver.try_recv() {
+				match m {
+					Message::Progress(new_bytes) => *bytes = new_bytes,
+					Message::Finished(r) => result = Some(r),
+				}
+			}
+		}
+
+		if let Some(result) = result {
+			self.status = Status::Done(result);
+			return true;
+		}
+
+		false
+	}
+}
+
+fn relocate(source: &Path, target: &Path, sender: &mut Sender<Message>) -> io::Result<()> {
+	if target.symlink_metadata().is_ok() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", target.to_string_lossy())));
+	}
+
+	match fs::rename(source, target) {
+		Ok(()) => return Ok(()),
+		Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {},
+		Err(e) => return Err(e),
+	}
+
+	// different volumes: copy, read the copy back, and only then remove the original
+	let mut copied = 0;
+	let result = copy_recursive(source, target, sender, &mut copied).and_then(|_| verify_recursive(source, target));
+	if let Err(e) = result {
+		let _ = remove_path(target);
+		return Err(e);
+	}
+
+	remove_path(source)
+}
+
+fn copy_recursive(source: &Path, target: &Path, sender: &mut Sender<Message>, copied: &mut u64) -> io::Result<()> {
+	let metadata = source.symlink_metadata()?;
+
+	if metadata.is_symlink() {
+		copy_symlink(source, target)?;
+	}
+	else if metadata.is_dir() {
+		fs::create_dir(target)?;
+		for entry in fs::read_dir(source)? {
+			let entry = entry?;
+			copy_recursive(&entry.path(), &target.join(entry.file_name()), sender, copied)?;
+		}
+		fs::set_permissions(target, metadata.permissions())?;
+	}
+	else if metadata.is_file() {
+		let mut from = File::open(source)?;
+		let mut to = File::create(target)?;
+		let mut buffer = vec![0; BUFFER_BYTES];
+
+		loop {
+			let read = from.read(&mut buffer)?;
+			if read == 0 {
+				break;
+			}
+			to.write_all(&buffer[..read])?;
+
+			*copied += read as u64;
+			sender.send(Message::Progress(*copied)).unwrap();
+		}
+
+		to.sync_all()?;
+		if let Ok(modified) = metadata.modified() {
+			to.set_modified(modified)?;
+		}
+		fs::set_permissions(target, metadata.permissions())?;
+	}
+	else {
+		// opening a pipe or device would block or read forever
+		copy_special(source, target, &metadata)?;
+	}
+
+	Ok(())
+}
+
+// pipes are made again, devices and sockets stop the move as they can't be recreated without privileges or their process
+#[cfg(unix)]
+fn copy_special(source: &Path, target: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+	use std::{os::unix::fs::{FileTypeExt, PermissionsExt}, process::Command};
+
+	if !metadata.file_type().is_fifo() {
+		return Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is a device or socket and can't be moved to another volume", source.to_string_lossy())));
+	}
+
+	// std has no mkfifo without linking libc
+	let mode = format!("{:o}", metadata.permissions().mode() & 0o7777);
+	if !Command::new("mkfifo").arg("-m").arg(mode).arg("--").arg(target).status()?.success() {
+		return Err(io::Error::other(format!("couldn't create the pipe {}", target.to_string_lossy())));
+	}
+	Ok(())
+}
+
+#[cfg(not(unix))]
+fn copy_special(source: &Path, _target: &Path, _metadata: &fs::Metadata) -> io::Result<()> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, format!("{} is a special file and can't be moved to another volume", source.to_string_lossy())))
+}
+
+#[cfg(unix)]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+	std::os::unix::fs::symlink(fs::read_link(source)?, target)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(source: &Path, target: &Path) -> io::Result<()> {
+	fs::copy(source, target).map(|_| ())
+}
+
+// compares the structure and the contents of both trees
+fn verify_recursive(source: &Path, target: &Path) -> io::Result<()> {
+	let metadata = source.symlink_metadata()?;
+	let mismatch = || io::Error::new(io::ErrorKind::InvalidData, format!("verification failed for {}", target.to_string_lossy()));
+
+	if metadata.is_symlink() {
+		if fs::read_link(source)? != fs::read_link(target)? {
+			return Err(mismatch());
+		}
+	}
+	else if metadata.is_dir() {
+		let mut source_names: Vec<_> = fs::read_dir(source)?.map(|e| e.map(|e| e.file_name())).collect::<io::Result<_>>()?;
+		let mut target_names: Vec<_> = fs::read_dir(target)?.map(|e| e.map(|e| e.file_name())).collect::<io::Result<_>>()?;
+		source_names.sort();
+		target_names.sort();
+		if source_names != target_names {
+			return Err(mismatch());
+		}
+
+		for name in source_names {
+			verify_recursive(&source.join(&name), &target.join(&name))?;
+		}
+	}
+	else if metadata.is_file() {
+		if hash_file(source)? != hash_file(target)? {
+			return Err(mismatch());
+		}
+	}
+	else if target.symlink_metadata()?.file_type() != metadata.file_type() {
+		return Err(mismatch());
+	}
+
+	Ok(())
+}
+
+fn hash_file(path: &Path) -> io::Result<(u64, u64)> {
+	let mut file = File::open(path)?;
+	let mut hasher = DefaultHasher::new();
+	let mut buffer = vec![0; BUFFER_BYTES];
+	let mut length = 0;
+
+	loop {
+		let read = file.read(&mut buffer)?;
+		if read == 0 {
+			break;
+		}
+		hasher.write(&buffer[..read]);
+		length += read as u64;
+	}
+
+	Ok((length, hasher.finish()))
+}
\ No newline at end of file
@@ -0,0 +1,240 @@
+use std::{path::Path, io::{self, Write, BufWriter}, fs::File};
+
+use egui_macroquad::{egui, macroquad::prelude::Color};
+use fsexpl_core::{TreeNode, Weight, export::Filter, layout::{self, Rect, Tile, Content}, text::bytes_to_text};
+
+use crate::helper::col_from_hsv;
+
+// the treemap as an image file, drawn on the cpu so it works on servers without a display or gpu
+// the labels use the monospace font egui ships, so both formats fit text the same way
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+	Svg,
+	Png,
+}
+
+impl ImageFormat {
+	pub fn from_path(path: &Path) -> Option<Self> {
+		match path.extension()?.to_str()?.to_ascii_lowercase().as_str() {
+			"svg" => Some(Self::Svg),
+			"png" => Some(Self::Png),
+			_ => None,
+		}
+	}
+}
+
+// directories smaller than this on screen are drawn as a single tile
+const MIN_SIDE: f32 = 12.0;
+const MAX_FONT_SIZE: f32 = 16.0;
+const MIN_FONT_SIZE: f32 = 7.0;
+const CHAR_WIDTH: f32 = 0.6; // of the font size, for the monospace font
+
+pub fn render<N: TreeNode>(root: &N, filter: &Filter, width: u32, height: u32, out: &Path) -> io::Result<()> {
+	let rect = Rect::new(0.0, 0.0, width as f32, height as f32);
+	let tiles = layout::tiles(root, filter, Weight::Bytes, rect, MIN_SIDE);
+	let mut writer = BufWriter::new(File::create(out)?);
+
+	match ImageFormat::from_path(out) {
+		Some(ImageFormat::Svg) => write_svg(&tiles, width, height, &mut writer)?,
+		Some(ImageFormat::Png) => write_png(&tiles, width, height, &mut writer)?,
+		None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "the image has to end in .svg or .png")),
+	}
+	writer.flush()
+}
+
+// a line of text with its baseline at y, centered on x or starting there
+struct Label {
+	text: String,
+	x: f32,
+	y: f32,
+	size: f32,
+	centered: bool,
+}
+
+// the tile color like in the window, bright directories and dark files, but stable between runs
+fn color(tile: &Tile) -> Color {
+	let Content::Entry(entry) = tile.content else {
+		return Color::new(0.27, 0.27, 0.27, 1.0);
+	};
+
+	// fnv-1a of the path
+	let hash = entry.get_full_path().bytes().fold(0x811c9dc5u32, |hash, b| (hash ^ b as u32).wrapping_mul(0x01000193));
+	let hue = (hash % 360) as f32 / 360.0;
+	let saturation = 0.4 + ((hash >> 9) % 60) as f32 / 100.0;
+	let brightness = if entry.is_dir() { 1.0 } else { 0.15 } * if tile.expanded { 0.7 } else { 1.0 };
+
+	col_from_hsv(hue, saturation, brightness)
+}
+
+// the name and size in the middle of closed tiles, the name in the top frame of expanded directories
+fn labels(tile: &Tile) -> Vec<Label> {
+	let rect = tile.rect;
+	let (name, size_text) = match tile.content {
+		Content::Entry(entry) if tile.level == 0 => (entry.get_full_path(), bytes_to_text(entry.bytes())),
+		Content::Entry(entry) => (entry.name().to_string(), bytes_to_text(entry.bytes())),
+		Content::Omitted { count, bytes } => (format!("{count} smaller"), bytes_to_text(bytes)),
+	};
+
+	if tile.expanded {
+		let frame = rect.inner().y - rect.y;
+		let size = (frame * 0.7).min(MAX_FONT_SIZE);
+		if size < MIN_FONT_SIZE {
+			return Vec::new();
+		}
+		let text = fit(&format!("{name}  {size_text}"), rect.w - 2.0 * frame, size);
+		return vec![Label { text, x: rect.x + frame, y: rect.y + frame * 0.5 + size * 0.35, size, centered: false }];
+	}
+
+	let size = MAX_FONT_SIZE.min(rect.h / 3.0).min(rect.w / 4.0);
+	if size < MIN_FONT_SIZE {
+		return Vec::new();
+	}
+	let center = (rect.x + rect.w / 2.0, rect.y + rect.h / 2.0);
+	vec![
+		Label { text: fit(&name, rect.w - 4.0, size), x: center.0, y: center.1 - size * 0.2, size, centered: true },
+		Label { text: fit(&size_text, rect.w - 4.0, size * 0.85), x: center.0, y: center.1 + size, size: size * 0.85, centered: true },
+	]
+}
+
+// the text cut to the width with an ellipsis
+fn fit(text: &str, width: f32, size: f32) -> String {
+	let chars = (width / (size * CHAR_WIDTH)).max(0.0) as usize;
+	if text.chars().count() <= chars {
+		text.to_string()
+	}
+	else if chars > 1 {
+		text.chars().take(chars - 1).chain(std::iter::once('…')).collect()
+	}
+	else {
+		String::new()
+	}
+}
+
+fn write_svg(tiles: &[Tile], width: u32, height: u32, writer: &mut impl Write) -> io::Result<()> {
+	writeln!(writer, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">")?;
+	writeln!(writer, "<style>text {{ fill: #fff; font-family: Hack, \"DejaVu Sans Mono\", monospace; }}</style>")?;
+
+	for tile in tiles {
+		let r = tile.rect;
+		let c = color(tile);
+		writeln!(
+			writer,
+			"<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"rgb({},{},{})\" stroke=\"#000\" stroke-width=\"0.5\"/>",
+			r.x, r.y, r.w.max(0.0), r.h.max(0.0), (c.r * 255.0) as u8, (c.g * 255.0) as u8, (c.b * 255.0) as u8,
+		)?;
+	}
+
+	// labels last, so no tile covers them
+	for label in tiles.iter().flat_map(labels).filter(|l| !l.text.is_empty()) {
+		let anchor = if label.centered { " text-anchor=\"middle\"" } else { "" };
+		writeln!(writer, "<text x=\"{:.2}\" y=\"{:.2}\" font-size=\"{:.1}\"{anchor}>{}</text>", label.x, label.y, label.size, escape_xml(&label.text))?;
+	}
+
+	writeln!(writer, "</svg>")
+}
+
+fn escape_xml(text: &str) -> String {
+	text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+fn write_png(tiles: &[Tile], width: u32, height: u32, writer: &mut impl Write) -> io::Result<()> {
+	let mut canvas = Canvas { width: width as usize, height: height as usize, pixels: vec![0; width as usize * height as usize * 3] };
+
+	for tile in tiles {
+		canvas.fill(tile.rect, color(tile));
+		canvas.outline(tile.rect);
+	}
+
+	let fonts = egui::FontDefinitions::default();
+	let font = fonts.font_data.get("Hack")
+		.and_then(|data| fontdue::Font::from_bytes(&*data.font, fontdue::FontSettings::default()).ok());
+	if let Some(font) = font {
+		for label in tiles.iter().flat_map(labels) {
+			canvas.text(&font, &label);
+		}
+	}
+
+	let mut encoder = png::Encoder::new(writer, width, height);
+	encoder.set_color(png::ColorType::Rgb);
+	encoder.set_depth(png::BitDepth::Eight);
+	encoder.write_header()
+		.and_then(|mut image| image.write_image_data(&canvas.pixels))
+		.map_err(io::Error::other)
+}
+
+// rgb pixels, rows from the top
+struct Canvas {
+	width: usize,
+	height: usize,
+	pixels: Vec<u8>,
+}
+
+impl Canvas {
+	// whole pixels from the rounded edges, so neighbouring tiles neither overlap nor leave gaps
+	fn span(&self, rect: Rect) -> (usize, usize, usize, usize) {
+		let x0 = (rect.x.round().max(0.0) as usize).min(self.width);
+		let y0 = (rect.y.round().max(0.0) as usize).min(self.height);
+		let x1 = ((rect.x + rect.w).round().max(0.0) as usize).min(self.width);
+		let y1 = ((rect.y + rect.h).round().max(0.0) as usize).min(self.height);
+		(x0, y0, x1, y1)
+	}
+
+	fn fill(&mut self, rect: Rect, color: Color) {
+		let (x0, y0, x1, y1) = self.span(rect);
+		let rgb = [(color.r * 255.0) as u8, (color.g * 255.0) as u8, (color.b * 255.0) as u8];
+		for y in y0..y1 {
+			for x in x0..x1 {
+				let i = (y * self.width + x) * 3;
+				self.pixels[i..i + 3].copy_from_slice(&rgb);
+			}
+		}
+	}
+
+	// a black line on the top and left edges, the neighbours draw the others
+	fn outline(&mut self, rect: Rect) {
+		let (x0, y0, x1, y1) = self.span(rect);
+		if x0 >= x1 || y0 >= y1 {
+			return;
+		}
+		for x in x0..x1 {
+			self.blend(x, y0, 0, 0.6);
+		}
+		for y in y0 + 1..y1 {
+			self.blend(x0, y, 0, 0.6);
+		}
+	}
+
+	fn blend(&mut self, x: usize, y: usize, value: u8, alpha: f32) {
+		if x >= self.width || y >= self.height {
+			return;
+		}
+		let i = (y * self.width + x) * 3;
+		for channel in &mut self.pixels[i..i + 3] {
+			*channel = (*channel as f32 * (1.0 - alpha) + value as f32 * alpha) as u8;
+		}
+	}
+
+	fn text(&mut self, font: &fontdue::Font, label: &Label) {
+		let width: f32 = label.text.chars().map(|c| font.metrics(c, label.size).advance_width).sum();
+		let mut pen = if label.centered { label.x - width / 2.0 } else { label.x };
+
+		for c in label.text.chars() {
+			let (metrics, coverage) = font.rasterize(c, label.size);
+			// the bitmap starts ymin below the baseline and extends upwards
+			let left = (pen + metrics.xmin as f32).round() as isize;
+			let top = (label.y - metrics.ymin as f32 - metrics.height as f32).round() as isize;
+
+			for row in 0..metrics.height {
+				for column in 0..metrics.width {
+					let alpha = coverage[row * metrics.width + column] as f32 / 255.0;
+					let (x, y) = (left + column as isize, top + row as isize);
+					if alpha > 0.0 && x >= 0 && y >= 0 {
+						self.blend(x as usize, y as usize, 255, alpha);
+					}
+				}
+			}
+			pen += metrics.advance_width;
+		}
+	}
+}
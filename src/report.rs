@@ -0,0 +1,111 @@
+use std::{path::Path, io::{self, Write}, fs::File};
+
+use fsexpl_core::{Node, scan::{self, ScanOptions}, export::{self, Format, Filter}, text::bytes_to_text};
+
+use crate::render;
+
+// scans the path and prints its largest entries as an indented tree, like du -h --max-depth
+// returns the process exit code
+pub fn run(path: &Path, options: &ScanOptions, top: usize, depth: usize) -> i32 {
+	let Some((root, complete)) = scan_reporting_denied(path, options) else {
+		return 1;
+	};
+
+	let mut out = io::BufWriter::new(io::stdout().lock());
+	let result = writeln!(out, "{:>9}  {}", bytes_to_text(root.entry.bytes()), root.entry.get_full_path())
+		.and_then(|_| write_children(&mut out, &root, top, depth, 1))
+		.and_then(|_| out.flush());
+
+	match result {
+		Ok(()) => if complete { 0 } else { 1 },
+		// a closed pipe (report | head) is not worth complaining about
+		Err(e) if e.kind() == io::ErrorKind::BrokenPipe => 0,
+		Err(e) => {
+			eprintln!("fsexpl: {e}");
+			1
+		},
+	}
+}
+
+fn write_children(out: &mut impl Write, node: &Node, top: usize, depth: usize, level: usize) -> io::Result<()> {
+	if level > depth {
+		return Ok(());
+	}
+
+	let mut children: Vec<&Node> = node.children.iter().collect();
+	children.sort_by(|a, b| b.entry.bytes().cmp(&a.entry.bytes()).then_with(|| a.entry.name().cmp(b.entry.name())));
+
+	let indent = "  ".repeat(level);
+	for child in children.iter().take(top) {
+		let slash = if child.entry.is_dir() { "/" } else { "" };
+		writeln!(out, "{:>9}  {indent}{}{slash}", bytes_to_text(child.entry.bytes()), child.entry.name())?;
+		write_children(out, child, top, depth, level + 1)?;
+	}
+
+	if children.len() > top {
+		let rest = &children[top..];
+		let bytes = rest.iter().map(|c| c.entry.bytes()).sum();
+		writeln!(out, "{:>9}  {indent}({} more)", bytes_to_text(bytes), rest.len())?;
+	}
+
+	Ok(())
+}
+
+// scans the path and writes it in the given format to the file, or to standard output
+pub fn export(path: &Path, options: &ScanOptions, format: Format, out: Option<&Path>, export_options: &export::Options) -> i32 {
+	let Some((root, complete)) = scan_reporting_denied(path, options) else {
+		return 1;
+	};
+
+	let export_options = &export::Options { owner_names: scan::is_local(path), ..export_options.clone() };
+	let result = match out {
+		Some(out) => File::create(out).and_then(|file| {
+			let mut writer = io::BufWriter::new(file);
+			export::write(&root, format, export_options, &mut writer)?;
+			writer.flush()
+		}),
+		None => {
+			let mut writer = io::BufWriter::new(io::stdout().lock());
+			export::write(&root, format, export_options, &mut writer).and_then(|_| writer.flush())
+		},
+	};
+
+	match result {
+		Ok(()) => if complete { 0 } else { 1 },
+		Err(e) if e.kind() == io::ErrorKind::BrokenPipe => 0,
+		Err(e) => {
+			eprintln!("fsexpl: cannot export: {e}");
+			1
+		},
+	}
+}
+
+// scans the path and draws the treemap into an svg or png image
+pub fn render(path: &Path, options: &ScanOptions, out: &Path, filter: &Filter, width: u32, height: u32) -> i32 {
+	let Some((root, complete)) = scan_reporting_denied(path, options) else {
+		return 1;
+	};
+
+	if let Err(e) = render::render(&root, filter, width, height, out) {
+		eprintln!("fsexpl: cannot render {}: {e}", out.to_string_lossy());
+		return 1;
+	}
+
+	if complete { 0 } else { 1 }
+}
+
+// the tree and whether every path could be read, unreadable paths are listed on stderr
+fn scan_reporting_denied(path: &Path, options: &ScanOptions) -> Option<(Node, bool)> {
+	let mut denied = Vec::new();
+	let root = scan::scan_blocking(path, options, &mut denied);
+
+	for path in &denied {
+		eprintln!("fsexpl: cannot read {}", path.to_string_lossy());
+	}
+
+	if root.is_none() {
+		eprintln!("fsexpl: cannot scan {}", path.to_string_lossy());
+	}
+
+	root.map(|root| (root, denied.is_empty()))
+}
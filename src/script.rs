@@ -0,0 +1,225 @@
+use std::{fs, io, path::Path, time::{SystemTime, UNIX_EPOCH}};
+
+use fsexpl_core::{text::{bytes_to_text, time_to_text}, scan::{ScanOptions, SizeMode}};
+
+use crate::{basket::Action, trash::percent_encode};
+
+// a POSIX shell script that removes the given paths, each guarded by a check that its size didn't change since the scan
+pub fn cleanup_script(items: &[(String, u64)], action: Action, options: &ScanOptions) -> String {
+	let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+	let total = items.iter().map(|(_, bytes)| bytes).sum();
+
+	let mut script = String::new();
+	script.push_str("#!/bin/sh\n");
+	script.push_str(&format!("# cleanup plan exported by FsExpl on {} UTC\n", time_to_text(seconds)));
+	script.push_str(&format!("# {} paths, {} reclaimable\n", items.len(), bytes_to_text(total)));
+	script.push_str("#\n");
+	script.push_str("# Review every command before running it. A path is skipped if it no longer\n");
+	script.push_str("# exists or its size differs from the scanned one.\n\n");
+	script.push_str("set -u\n\n");
+
+	if action == Action::Trash {
+		script.push_str("TRASH_DIR=\"${TRASH_DIR:-${XDG_DATA_HOME:-$HOME/.local/share}/Trash}\"\n");
+		script.push_str("mkdir -p \"$TRASH_DIR/files\" \"$TRASH_DIR/info\" || exit 1\n\n");
+	}
+
+	script.push_str(&size_of(options));
+	script.push_str(GUARD);
+	if action == Action::Trash {
+		script.push_str(TRASH);
+	}
+	script.push('\n');
+
+	for (i, (path, bytes)) in items.iter().enumerate() {
+		let quoted = quote(path);
+		script.push_str(&format!("# {}\n", bytes_to_text(*bytes)));
+
+		match action {
+			Action::Delete => {
+				script.push_str(&format!("unchanged {quoted} {bytes} && rm -rf -- {quoted} || failed=$((failed + 1))\n\n"));
+			},
+			Action::Trash => {
+				let name = Path::new(path).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+				let target = quote(&format!("fsexpl-{seconds}-{}-{name}", i + 1));
+				let encoded = quote(&percent_encode(Path::new(path)));
+				script.push_str(&format!("unchanged {quoted} {bytes} && trash {quoted} {target} {encoded} || failed=$((failed + 1))\n\n"));
+			},
+		}
+	}
+
+	script.push_str("if [ \"$failed\" -ne 0 ]; then\n");
+	script.push_str("\techo \"$failed paths were not removed\" >&2\n");
+	script.push_str("\texit 1\n");
+	script.push_str("fi\n");
+
+	script
+}
+
+pub fn write_cleanup_script(path: &Path, items: &[(String, u64)], action: Action, options: &ScanOptions) -> io::Result<()> {
+	fs::write(path, cleanup_script(items, action, options))?;
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+	}
+
+	Ok(())
+}
+
+// sums sizes the same way the scan counted them, so the guard compares like with like
+// only POSIX find, ls and awk, the script is meant for other machines too; the sizes are the leading columns of ls,
+// which a name with a newline can't shift, its extra line only makes the guard fail
+// in disk mode ls -s counts whole KiB, the same as the scan unless a file has an odd number of 512 byte blocks
+fn size_of(options: &ScanOptions) -> String {
+	let mut find = String::from("find ");
+	if options.follow_symlinks {
+		find.push_str("-L ");
+	}
+	find.push_str("\"$1\"");
+	if options.one_filesystem {
+		find.push_str(" -xdev");
+	}
+
+	// full paths, or bare names matched anywhere, left out with everything below them
+	let excludes: Vec<String> = options.excludes.iter().map(|e| {
+		let primary = if e.components().count() == 1 { "-name" } else { "-path" };
+		format!("{primary} {}", quote(&pattern(&e.to_string_lossy())))
+	}).collect();
+	if !excludes.is_empty() {
+		find.push_str(&format!(" \\( {} \\) -prune -o", excludes.join(" -o ")));
+	}
+
+	// directories count nothing of their own, symlinks count themselves unless followed, devices and pipes have no length
+	let (types, ls, column) = match (options.size_mode, options.follow_symlinks) {
+		(SizeMode::Apparent, true) => ("-type f", "ls -ldnL", "$5"),
+		(SizeMode::Apparent, false) => ("\\( -type f -o -type l \\)", "ls -ldn", "$5"),
+		(SizeMode::Disk, true) => ("! -type d", "ls -ldnskL", "$1 * 1024"),
+		(SizeMode::Disk, false) => ("! -type d", "ls -ldnsk", "$1 * 1024"),
+	};
+
+	format!("size_of() {{\n\t{find} {types} -exec {ls} -- {{}} + | awk '{{ s += {column} }} END {{ printf \"%.0f\\n\", s }}'\n}}\n\n")
+}
+
+const GUARD: &str = r#"failed=0
+
+unchanged() {
+	if [ ! -e "$1" ]; then
+		echo "skipping $1: no longer exists" >&2
+		return 1
+	fi
+	actual=$(size_of "$1")
+	if [ "$actual" -ne "$2" ]; then
+		echo "skipping $1: size changed from $2 to $actual bytes" >&2
+		return 1
+	fi
+}
+"#;
+
+// the freedesktop.org layout, the .trashinfo file lets file managers restore the item
+// creating it with noclobber reserves the name
+const TRASH: &str = r#"
+trash() {
+	info="$TRASH_DIR/info/$2.trashinfo"
+	if [ -e "$TRASH_DIR/files/$2" ] || ! (set -C; printf '[Trash Info]\nPath=%s\nDeletionDate=%s\n' "$3" "$(date +%Y-%m-%dT%H:%M:%S)" > "$info") 2>/dev/null; then
+		echo "skipping $1: $2 is already in the trash" >&2
+		return 1
+	fi
+	mv -- "$1" "$TRASH_DIR/files/$2" || { rm -f -- "$info"; return 1; }
+}
+"#;
+
+// single quotes protect everything except single quotes themselves
+fn quote(text: &str) -> String {
+	format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+// a find pattern matching only the text itself
+fn pattern(text: &str) -> String {
+	text.chars().fold(String::new(), |mut pattern, c| {
+		if matches!(c, '*' | '?' | '[' | '\\') {
+			pattern.push('\\');
+		}
+		pattern.push(c);
+		pattern
+	})
+}
+#[cfg(test)]
+mod tests {
+	use std::{env, fs, path::{Path, PathBuf}, process::{self, Command, Output}};
+
+	use fsexpl_core::scan;
+
+	use super::*;
+
+	fn temp_dir(name: &str) -> PathBuf {
+		let dir = env::temp_dir().join(format!("fsexpl-script-{name}-{}", process::id()));
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		dir
+	}
+
+	fn sh(script: &str, args: &[&Path]) -> Output {
+		Command::new("sh").arg("-c").arg(script).arg("sh").args(args).output().unwrap()
+	}
+
+	#[test]
+	fn quoting_survives_the_shell() {
+		for text in ["plain", "it's", "a b  c", "$(touch pwned) `id` $HOME", "'\\'\"\n\t*?[x]"] {
+			let output = sh(&format!("printf %s {}", quote(text)), &[]);
+			assert_eq!(String::from_utf8_lossy(&output.stdout), text);
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn guard_sums_like_the_scan() {
+		let dir = temp_dir("guard");
+		fs::create_dir_all(dir.join("sub/cache")).unwrap();
+		fs::write(dir.join("a"), [1; 1000]).unwrap();
+		fs::write(dir.join("sub/b"), [2; 5000]).unwrap();
+		fs::write(dir.join("sub/cache/c"), [3; 300]).unwrap();
+		fs::write(dir.join("odd 'name\" -x"), [4; 70]).unwrap();
+		fs::write(dir.join("star*"), [5; 10]).unwrap();
+		fs::write(dir.join("starry"), [6; 20]).unwrap();
+		fs::hard_link(dir.join("sub/b"), dir.join("hard")).unwrap();
+		std::os::unix::fs::symlink("sub/b", dir.join("link")).unwrap();
+
+		let excludes = [Vec::new(), vec![dir.join("sub/cache"), PathBuf::from("star*")]];
+		for (size_mode, follow_symlinks, excludes) in [SizeMode::Apparent, SizeMode::Disk].into_iter()
+			.flat_map(|mode| [(mode, true), (mode, false)])
+			.flat_map(|(mode, follow)| excludes.iter().map(move |e| (mode, follow, e.clone())))
+		{
+			let options = ScanOptions { size_mode, follow_symlinks, excludes, one_filesystem: true, ..Default::default() };
+			let expected = scan::scan_blocking(&dir, &options, &mut Vec::new()).unwrap().entry.bytes();
+
+			let output = sh(&format!("{}size_of \"$1\"", size_of(&options)), &[&dir]);
+			let actual = String::from_utf8_lossy(&output.stdout);
+			assert_eq!(actual.trim(), expected.to_string(), "{size_of}", size_of = size_of(&options));
+		}
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn script_skips_changed_paths() {
+		let dir = temp_dir("skip");
+		fs::write(dir.join("same"), [1; 100]).unwrap();
+		fs::write(dir.join("grown"), [2; 200]).unwrap();
+		let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+		let items = [(path("same"), 100), (path("grown"), 150), (path("gone"), 10)];
+
+		let output = sh(&cleanup_script(&items, Action::Delete, &ScanOptions::default()), &[]);
+		let errors = String::from_utf8_lossy(&output.stderr);
+
+		assert!(!dir.join("same").exists());
+		assert!(dir.join("grown").exists());
+		assert!(errors.contains("size changed from 150 to 200"), "{errors}");
+		assert!(errors.contains("no longer exists"), "{errors}");
+		assert!(errors.contains("2 paths were not removed"), "{errors}");
+		assert_eq!(output.status.code(), Some(1));
+
+		fs::remove_dir_all(&dir).unwrap();
+	}
+}
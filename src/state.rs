@@ -1,37 +1,142 @@
-use std::path::{PathBuf, Path};
+use std::{path::{PathBuf, Path}, collections::HashSet, fs::File, io::{self, BufWriter, Write}};
 
-use egui_macroquad::{macroquad::prelude::*, egui::{Pos2, TextEdit, Window, ScrollArea}};
-use crate::{controls::Controls, tree::Tree, helper::{draw_centered_text, bytes_to_text}, icon::Icon};
+use egui_macroquad::{macroquad::prelude::*, egui::{Pos2, TextEdit, Window, ScrollArea, CollapsingHeader, Ui, Button, Align2, Color32, Area, Order, Frame, Key, ComboBox, DragValue}};
+use fsexpl_core::{scan::{self, ScanOptions, SizeMode}, du::{Units, DirSizes}, archive, text::{bytes_to_text, text_to_bytes}, snapshot, export::{self, Format}, csv::{Column, Rows}, Weight, TreeNode};
 
+use crate::{controls::{Controls, Gesture}, tree::{Tree, Rescan}, helper::draw_centered_text, icon::Icon, duplicates::Duplicates, details::Details, node::Node, trash::{self, TrashedItem}, basket::{Basket, Action}, script, open::{self, Commands}, relocate::{Relocation, Status as RelocationStatus}, picker::DirPicker, compress::{Compression, Status as CompressionStatus}, args::{Args, default_path}};
+
+// actions on a single node, collected while drawing the ui and applied afterwards
+#[derive(Clone, Copy, Debug)]
+enum NodeAction {
+	Expand,
+	Collapse, // itself if expanded, otherwise its parent
+	ZoomTo,
+	Rescan,
+	Trash,
+	ToggleBasket,
+	Exclude,
+	MoveTo,
+	Compress,
+	OpenArchive, // rescans an archive file as a directory of its contents
+}
 
 pub struct State {
 	controls: Controls,
 	denied: Vec<PathBuf>,
 	tree: Tree,
+	duplicates: Duplicates,
 	last_mouse_move: f64, // determine if should show tooltip
-	dragged_since_rmb_down: Vec2, // determine if hovered folder should collapse
+	dragged_since_rmb_down: Vec2, // determine if the context menu should open
 	path_input_buffer: String,
 	icon: Icon,
+	selected: Option<String>, // full path of the selected node
+	local: bool, // the tree was scanned from the local disk, not loaded from a file or listed remotely
+	weight: Weight,
+	storage_class_colors: bool, // objects of a bucket colored by their storage class
+	details: Option<Details>,
+	trashed: Vec<(TrashedItem, Node)>, // undo stack
+	basket: Basket,
+	dry_run: Option<Vec<String>>,
+	script_path_buffer: String,
+	snapshot_path_buffer: String,
+	export_path_buffer: String,
+	export_format: Format,
+	export_options: export::Options,
+	export_min_size_buffer: String,
+	commands: Commands,
+	options: ScanOptions,
+	rescans: Vec<Rescan>,
+	relocations: Vec<Relocation>,
+	move_picker: Option<(String, DirPicker)>, // source path, destination
+	compressions: Vec<Compression>,
+	compress_prompt: Option<(String, bool)>, // directory, delete original
+	menu: Option<(String, Pos2)>, // context menu target and position
+	pointer_over_ui: bool, // from the previous frame, ui windows take precedence over the tree
+	keyboard_over_ui: bool,
+	error: Option<String>, // result of the last failed action
 }
 
 impl State {
-	pub fn new() -> Self {
-		Self {
+	pub fn new(args: Args) -> Self {
+		let path = args.path.clone().unwrap_or_else(default_path);
+
+		let mut state = Self {
 			icon: Icon::new(Rect::new(0.25, 0.2, 0.5, 0.3)),
 			controls: Controls::new(),
 			denied: Vec::new(),
 			tree: Tree::Empty,
+			duplicates: Duplicates::Empty,
 			last_mouse_move: 0.0,
 			dragged_since_rmb_down: vec2(0.0, 0.0),
-			path_input_buffer: "C:".into(),
+			path_input_buffer: path.to_string_lossy().into(),
+			selected: None,
+			local: false,
+			weight: Weight::Bytes,
+			storage_class_colors: false,
+			details: None,
+			trashed: Vec::new(),
+			basket: Basket::new(),
+			dry_run: None,
+			script_path_buffer: "cleanup.sh".into(),
+			snapshot_path_buffer: "scan.fsexpl".into(),
+			export_path_buffer: "scan.json".into(),
+			export_format: Format::Json,
+			export_options: export::Options::default(),
+			export_min_size_buffer: "0".into(),
+			commands: Commands::new(),
+			options: args.options,
+			rescans: Vec::new(),
+			relocations: Vec::new(),
+			move_picker: None,
+			compressions: Vec::new(),
+			compress_prompt: None,
+			menu: None,
+			pointer_over_ui: false,
+			keyboard_over_ui: false,
+			error: None,
+		};
+
+		if args.path.is_some() {
+			state.scan();
 		}
+
+		state
+	}
+
+	// starts a fresh scan of the path in the input field, dropping everything tied to the old tree
+	fn scan(&mut self) {
+		self.tree.scan(Path::new(&self.path_input_buffer), &self.options);
+		self.local = scan::is_local(Path::new(&self.path_input_buffer));
+		self.export_options.owner_names = self.local;
+		self.duplicates = Duplicates::Empty;
+		self.selected = None;
+		self.details = None;
+		self.basket.clear();
+		self.dry_run = None;
+		self.menu = None;
+		self.rescans.clear();
+		self.icon.trigger_start();
 	}
 
 	pub fn frame(&mut self) {
 		if self.tree.process(&mut self.denied) {
 			self.icon.trigger_end();
+			self.color_storage_classes();
+		}
+
+		if self.duplicates.process(&mut self.denied) {
+			self.mark_duplicates();
 		}
 
+		let removed = self.basket.process();
+		if !removed.is_empty() {
+			self.remove_executed(removed);
+		}
+
+		self.process_relocations();
+		self.process_compressions();
+		self.process_rescans();
+
 		self.update_interactions();		
 
 		clear_background(BLACK);
@@ -42,6 +147,289 @@ impl State {
 		self.draw_ui(tooltip);
 	}
 
+	fn mark_duplicates(&mut self) {
+		if let (Tree::Ready(root, _, _), Duplicates::Ready(groups, _)) = (&mut self.tree, &self.duplicates) {
+			let paths: HashSet<PathBuf> = groups.iter().flat_map(|g| g.paths.iter().cloned()).collect();
+			root.mark_duplicates(&paths);
+		}
+	}
+
+	fn trash(&mut self, path: &str) {
+		let Tree::Ready(root, _, _) = &mut self.tree else {
+			return;
+		};
+		if path == root.get_full_path() {
+			return;
+		}
+
+		match trash::move_to_trash(Path::new(path)) {
+			Ok(item) => {
+				if let Some(node) = root.remove(path, self.weight) {
+					self.trashed.push((item, node));
+				}
+				self.tree.update_totals();
+				self.clear_missing_selection();
+				self.error = None;
+			},
+			Err(e) => self.error = Some(format!("{path}: {e}")),
+		}
+	}
+
+	fn undo_trash(&mut self) {
+		let Some((item, node)) = self.trashed.pop() else {
+			return;
+		};
+
+		match trash::restore(&item) {
+			Ok(()) => {
+				if let Tree::Ready(root, _, _) = &mut self.tree {
+					if let Err(node) = root.insert(node, self.weight) {
+						self.rescan_closest(Path::new(&node.get_full_path()));
+					}
+				}
+				self.tree.update_totals();
+				self.error = None;
+			},
+			Err(e) => {
+				self.error = Some(format!("{}: {e}", item.original.to_string_lossy()));
+				self.trashed.push((item, node));
+			},
+		}
+	}
+
+	fn remove_executed(&mut self, removed: Vec<(PathBuf, Option<TrashedItem>)>) {
+		if let Tree::Ready(root, _, _) = &mut self.tree {
+			for (path, item) in removed {
+				let path = path.to_string_lossy().to_string();
+				let node = root.remove(&path, self.weight);
+
+				if let (Some(item), Some(node)) = (item, node) {
+					self.trashed.push((item, node));
+				}
+			}
+
+		}
+		self.tree.update_totals();
+		self.clear_missing_selection();
+		self.sync_basket_marks();
+	}
+
+	// drops the selection if its node is no longer part of the tree
+	fn clear_missing_selection(&mut self) {
+		let missing = match (&self.tree, &self.selected) {
+			(Tree::Ready(root, _, _), Some(path)) => root.find(path).is_none(),
+			_ => true,
+		};
+
+		if missing {
+			self.selected = None;
+			self.details = None;
+			self.menu = None;
+		}
+	}
+
+	fn select(&mut self, path: String) {
+		if let Tree::Ready(root, _, _) = &mut self.tree {
+			root.select(Some(&path));
+			self.details = Details::new(root, &path, self.local);
+			self.selected = Some(path);
+		}
+	}
+
+	fn toggle_basket(&mut self, path: &str) {
+		let Tree::Ready(root, _, _) = &self.tree else {
+			return;
+		};
+
+		if self.basket.contains(path) {
+			self.basket.remove(path);
+		}
+		else if let Some(node) = root.find(path) {
+			self.basket.add(path.to_string(), node.bytes());
+		}
+		self.sync_basket_marks();
+	}
+
+	// new trees come with random colors
+	fn color_storage_classes(&mut self) {
+		if let (true, Tree::Ready(root, _, _)) = (self.storage_class_colors, &mut self.tree) {
+			root.color_storage_classes(true);
+		}
+	}
+
+	// for nodes whose parent left the tree meanwhile, the closest directory that's still there is scanned again
+	fn rescan_closest(&mut self, path: &Path) {
+		if let Tree::Ready(root, _, _) = &self.tree {
+			if let Some(ancestor) = path.ancestors().skip(1).find(|a| root.find(&a.to_string_lossy()).is_some()) {
+				self.rescans.push(Rescan::start(ancestor, &self.options));
+			}
+		}
+	}
+
+	fn process_rescans(&mut self) {
+		let mut finished = Vec::new();
+		self.rescans.retain_mut(|rescan| match rescan.process(&mut self.denied) {
+			Some(node) => {
+				finished.push(node);
+				false
+			},
+			None => true,
+		});
+
+		for node in finished.iter_mut().filter_map(Option::take) {
+			if let Err(node) = self.tree.replace(node, self.weight) {
+				self.rescan_closest(Path::new(&node.get_full_path()));
+			}
+		}
+
+		if finished.is_empty() {
+			return;
+		}
+		self.color_storage_classes();
+
+		if let Some(path) = self.selected.clone() {
+			self.clear_missing_selection();
+			if self.selected.is_some() {
+				self.select(path);
+			}
+		}
+		self.sync_basket_marks();
+		self.mark_duplicates();
+	}
+
+	fn start_move(&mut self) {
+		let Some((source, picker)) = self.move_picker.take() else {
+			return;
+		};
+		let bytes = self.tree.root().and_then(|root| root.find(&source)).map_or(0, |node| node.bytes());
+
+		self.relocations.push(Relocation::start(Path::new(&source), Path::new(&picker.dir), bytes));
+	}
+
+	fn start_compression(&mut self) {
+		let Some((dir, delete_original)) = self.compress_prompt.take() else {
+			return;
+		};
+		let bytes = self.tree.root().and_then(|root| root.find(&dir)).map_or(0, |node| node.bytes());
+
+		self.compressions.push(Compression::start(Path::new(&dir), bytes, delete_original));
+	}
+
+	fn process_relocations(&mut self) {
+		for relocation in &mut self.relocations {
+			relocation.process();
+		}
+
+		let (finished, running): (Vec<_>, Vec<_>) = self.relocations.drain(..).partition(|r| matches!(r.status, RelocationStatus::Done(_)));
+		self.relocations = running;
+
+		for Relocation { source, target, status, .. } in finished {
+			match status {
+				RelocationStatus::Done(Ok(())) => {
+					if let Tree::Ready(root, _, _) = &mut self.tree {
+						root.remove(&source.to_string_lossy(), self.weight);
+
+						// the destination only needs updating if it's part of the scan
+						let target_parent = target.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+						if root.find(&target_parent).is_some() {
+							self.rescans.push(Rescan::start(&target, &self.options));
+						}
+					}
+					self.tree.update_totals();
+					self.clear_missing_selection();
+					self.sync_basket_marks();
+				},
+				RelocationStatus::Done(Err(e)) => self.error = Some(format!("{}: {e}", source.to_string_lossy())),
+				RelocationStatus::Running(_, _) => {},
+			}
+		}
+	}
+
+	fn process_compressions(&mut self) {
+		let mut changed = false;
+
+		for compression in &mut self.compressions {
+			if compression.process() {
+				if let (CompressionStatus::Done(Ok(_)), Tree::Ready(root, _, _)) = (&compression.status, &mut self.tree) {
+					if compression.delete_original {
+						root.remove(&compression.source.to_string_lossy(), self.weight);
+						changed = true;
+					}
+					self.rescans.push(Rescan::start(&compression.archive, &self.options));
+				}
+			}
+		}
+
+		if changed {
+			self.tree.update_totals();
+			self.clear_missing_selection();
+		}
+	}
+
+	fn apply(&mut self, action: NodeAction, path: String) {
+		match action {
+			NodeAction::Expand => {
+				if let Tree::Ready(root, _, _) = &mut self.tree {
+					root.expand(&path, self.weight);
+				}
+			},
+			NodeAction::Collapse => self.collapse(&path),
+			NodeAction::ZoomTo => {
+				if let Some(node) = self.tree.root().and_then(|root| root.find(&path)) {
+					self.controls.zoom_to(node.big_rect());
+				}
+			},
+			NodeAction::Rescan => {
+				self.rescans.push(Rescan::start(Path::new(&path), &self.options));
+			},
+			NodeAction::OpenArchive => {
+				let options = ScanOptions { archives: true, ..self.options.clone() };
+				self.rescans.push(Rescan::start(Path::new(&path), &options));
+			},
+			NodeAction::Trash => self.trash(&path),
+			NodeAction::ToggleBasket => self.toggle_basket(&path),
+			NodeAction::MoveTo => {
+				let parent = Path::new(&path).parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+				self.move_picker = Some((path, DirPicker::new(parent)));
+			},
+			NodeAction::Compress => self.compress_prompt = Some((path, false)),
+			NodeAction::Exclude => {
+				if let Tree::Ready(root, _, _) = &mut self.tree {
+					if path != root.get_full_path() {
+						root.remove(&path, self.weight);
+						self.options.excludes.push(PathBuf::from(path));
+					}
+				}
+				self.tree.update_totals();
+				self.clear_missing_selection();
+			},
+		}
+	}
+
+	// an expanded directory collapses itself, anything else collapses its parent
+	fn collapse(&mut self, path: &str) {
+		let Tree::Ready(root, _, _) = &mut self.tree else {
+			return;
+		};
+		let Some(node) = root.find(path) else {
+			return;
+		};
+
+		if node.is_expanded() {
+			root.collapse(path);
+		}
+		else {
+			let parent = node.path_prefix().trim_end_matches(['/', '\\']).to_string();
+			root.collapse(&parent);
+		}
+	}
+
+	fn sync_basket_marks(&mut self) {
+		if let Tree::Ready(root, _, _) = &mut self.tree {
+			root.mark_basket(&self.basket);
+		}
+	}
+
 	fn update_interactions(&mut self) {
 		let mouse_delta = mouse_delta_position();
 
@@ -64,19 +452,41 @@ impl State {
 
 	fn tree_handle_mouse(&mut self) -> Option<(String, Vec2)> {
 		let mut tooltip = None;
-		if let Tree::Ready(root, _, _) = &mut self.tree {
-			let tooltip_text = root.handle_mouse(
-				*self.controls.mouse_world(), 
-				is_mouse_button_pressed(MouseButton::Left), 
-				is_mouse_button_released(MouseButton::Right) && self.dragged_since_rmb_down.length_squared() == 0.0
-			).0;
+		let Tree::Ready(root, _, _) = &mut self.tree else {
+			return None;
+		};
+
+		let (tooltip_text, hovered) = root.handle_mouse(*self.controls.mouse_world());
+		let pointer_free = !self.pointer_over_ui;
+		let keyboard_free = !self.keyboard_over_ui;
+
+		if pointer_free && (is_mouse_button_pressed(MouseButton::Left) || is_mouse_button_pressed(MouseButton::Right)) {
+			self.menu = None;
+		}
+
+		if let Some(path) = hovered {
+			if pointer_free && is_mouse_button_pressed(MouseButton::Left) {
+				self.select(path.clone());
+			}
+			if pointer_free && is_mouse_button_released(MouseButton::Right) && self.dragged_since_rmb_down.length_squared() == 0.0 {
+				self.select(path.clone());
+				let (x, y) = mouse_position();
+				self.menu = Some((path.clone(), Pos2::new(x, y)));
+			}
+
+			if self.controls.triggered(self.controls.bindings.expand, pointer_free, keyboard_free) {
+				self.apply(NodeAction::Expand, path.clone());
+			}
+			if self.controls.triggered(self.controls.bindings.collapse, pointer_free, keyboard_free) {
+				self.apply(NodeAction::Collapse, path);
+			}
+		}
 			
-			if get_time() - self.last_mouse_move > 2.0 {
-				if let Some(text) = tooltip_text {
-					let tooltip_pos = Vec2::from(mouse_position()) + vec2(10.0, 10.0);
+		if pointer_free && get_time() - self.last_mouse_move > 2.0 && self.menu.is_none() {
+			if let Some(text) = tooltip_text {
+				let tooltip_pos = Vec2::from(mouse_position()) + vec2(10.0, 10.0);
 
-					tooltip = Some((text, tooltip_pos));
-				}
+				tooltip = Some((text, tooltip_pos));
 			}
 		}
 
@@ -95,14 +505,14 @@ impl State {
 				draw_centered_text(&bytes_to_text(*bytes), 0.15, vec2(0.5, 0.7));
 				draw_centered_text(&format!("{files} files"), 0.15, vec2(0.5, 0.85));
 
-				self.icon.draw(&self.controls.camera(), None);
+				self.icon.draw(self.controls.camera(), None);
 			}
 			else if let Tree::Ready(node, bytes, files) = &self.tree {
 				draw_rectangle_lines(0.0, 0.0, 1.0, 1.0, 0.05, Color::new(0.5, 0.5, 0.5, 1.0));
 				draw_centered_text(&bytes_to_text(*bytes), 0.15, vec2(0.5, 0.7));
 				draw_centered_text(&format!("{files} files"), 0.15, vec2(0.5, 0.85));
 
-				self.icon.draw(&self.controls.camera(), Some(node));
+				self.icon.draw(self.controls.camera(), Some(node));
 			}
 		}
 		else if let Tree::Ready(root, _, _) = &self.tree {
@@ -113,6 +523,17 @@ impl State {
 	fn draw_ui(&mut self, tooltip: Option<(String, Vec2)>) {
 		set_camera(&Camera2D::from_display_rect(Rect::new(0.0, 0.0, screen_width(), screen_height())));
 
+		let mut actions = Vec::new();
+		let mut undo_clicked = false;
+		let mut basket_changed = false;
+		let mut open_error = None;
+		let mut menu_closed = false;
+		let mut move_clicked = false;
+		let mut move_cancelled = false;
+		let mut compress_clicked = false;
+		let mut compress_cancelled = false;
+		let mut dismissed_compression = None;
+
 		egui_macroquad::ui(|ctx| {
 			// draw tooltip
 			if let Some((text, pos)) = tooltip {
@@ -127,6 +548,75 @@ impl State {
 					});
 			}
 
+			// draw details of the selected node
+			if let Some(details) = &self.details {
+				Window::new("Details")
+					.anchor(Align2::RIGHT_TOP, [0.0, 0.0])
+					.resizable(false)
+					.show(ctx, |ui| {
+						details.draw(ui);
+
+						ui.horizontal_wrapped(|ui| {
+							self.draw_node_actions(ui, details.path(), false, &mut actions, &mut open_error);
+						});
+					});
+			}
+
+			// draw the destination picker of a move
+			if let Some((source, picker)) = &mut self.move_picker {
+				Window::new("Move to")
+					.collapsible(false)
+					.show(ctx, |ui| {
+						ui.label(format!("move {source} into:"));
+						picker.draw(ui);
+
+						ui.horizontal(|ui| {
+							if ui.button("Move here").clicked() {
+								move_clicked = true;
+							}
+							if ui.button("Cancel").clicked() {
+								move_cancelled = true;
+							}
+						});
+					});
+			}
+
+			// draw the confirmation of a compression
+			if let Some((dir, delete_original)) = &mut self.compress_prompt {
+				Window::new("Compress")
+					.collapsible(false)
+					.show(ctx, |ui| {
+						ui.label(format!("compress {dir} to {dir}.tar.zst"));
+						ui.checkbox(delete_original, "delete the original after verification");
+
+						ui.horizontal(|ui| {
+							if ui.button("Compress").clicked() {
+								compress_clicked = true;
+							}
+							if ui.button("Cancel").clicked() {
+								compress_cancelled = true;
+							}
+						});
+					});
+			}
+
+			// draw the context menu
+			if let Some((path, pos)) = &self.menu {
+				Area::new("context menu")
+					.fixed_pos(*pos)
+					.order(Order::Foreground)
+					.show(ctx, |ui| {
+						Frame::popup(ui.style()).show(ui, |ui| {
+							ui.set_min_width(150.0);
+							menu_closed = self.draw_node_actions(ui, path, true, &mut actions, &mut open_error);
+						});
+					});
+
+				if ctx.input(|i| i.key_pressed(Key::Escape)) {
+					menu_closed = true;
+				}
+			}
+
 			// draw sidebar
 			Window::new("sidebar")
 				.fixed_pos(Pos2::new(0.0, 0.0))
@@ -140,8 +630,139 @@ impl State {
 					ui.add(TextEdit::singleline(&mut self.path_input_buffer));
 
 					if ui.button("Scan").clicked() {
-						self.tree.scan(Path::new(&self.path_input_buffer));
-						self.icon.trigger_start();
+						self.scan();
+					}
+
+					ui.horizontal(|ui| {
+						ui.add(TextEdit::singleline(&mut self.snapshot_path_buffer).desired_width(120.0));
+						if ui.add_enabled(self.tree.root().is_some(), Button::new("Save snapshot")).clicked() {
+							if let Some(root) = self.tree.root() {
+								self.error = snapshot::save(root, Path::new(&self.snapshot_path_buffer))
+									.err()
+									.map(|e| format!("{}: {e}", self.snapshot_path_buffer));
+							}
+						}
+					});
+
+					if let Some(error) = &self.error {
+						ui.colored_label(Color32::RED, error);
+					}
+
+					for relocation in &self.relocations {
+						if let RelocationStatus::Running(bytes, _) = &relocation.status {
+							let name = relocation.source.file_name().unwrap_or_default().to_string_lossy();
+							ui.label(format!("moving {name}: {} of {}", bytes_to_text(*bytes), bytes_to_text(relocation.total)));
+						}
+					}
+
+					for (i, compression) in self.compressions.iter().enumerate() {
+						let name = compression.archive.file_name().unwrap_or_default().to_string_lossy();
+						let estimate = compression.estimate.map_or("?".into(), bytes_to_text);
+
+						match &compression.status {
+							CompressionStatus::Running(bytes, _) => {
+								ui.label(format!("compressing {name}: {} of {}, estimated {estimate}", bytes_to_text(*bytes), bytes_to_text(compression.total)));
+							},
+							CompressionStatus::Done(result) => {
+								ui.horizontal(|ui| {
+									if ui.small_button("x").clicked() {
+										dismissed_compression = Some(i);
+									}
+									match result {
+										Ok(size) => ui.label(format!("{name}: {}, estimated {estimate}", bytes_to_text(*size))),
+										Err(e) => ui.colored_label(Color32::RED, format!("{name}: {e}")),
+									};
+								});
+							},
+						}
+					}
+
+					if let Some((item, _)) = self.trashed.last() {
+						let name = item.original.file_name().unwrap_or_default().to_string_lossy();
+						undo_clicked = ui.button(format!("Undo trash of {name}")).clicked();
+					}
+
+					ui.label("Tile weight:");
+					ui.horizontal(|ui| {
+						let old_weight = self.weight;
+						ui.radio_value(&mut self.weight, Weight::Bytes, "bytes");
+						ui.radio_value(&mut self.weight, Weight::Count, "file count");
+
+						if self.weight != old_weight {
+							if let Tree::Ready(root, _, _) = &mut self.tree {
+								root.relayout(self.weight);
+							}
+						}
+					});
+					if ui.checkbox(&mut self.storage_class_colors, "color objects by storage class").changed() {
+						if let Tree::Ready(root, _, _) = &mut self.tree {
+							root.color_storage_classes(self.storage_class_colors);
+						}
+					}
+
+					CollapsingHeader::new("Bindings").show(ui, |ui| {
+						let bindings = &mut self.controls.bindings;
+						for (label, gesture) in [("expand", &mut bindings.expand), ("collapse", &mut bindings.collapse)] {
+							ComboBox::from_label(label)
+								.selected_text(gesture.name())
+								.show_ui(ui, |ui| {
+									for option in Gesture::ALL {
+										ui.selectable_value(gesture, option, option.name());
+									}
+								});
+						}
+						ui.label("right click opens the context menu");
+					});
+
+					CollapsingHeader::new("Export").show(ui, |ui| {
+						self.draw_export_panel(ui);
+					});
+
+					CollapsingHeader::new("Commands").show(ui, |ui| {
+						ui.label("{path} is the full path, {parent} the containing folder");
+						ui.label("containing folder:");
+						ui.add(TextEdit::singleline(&mut self.commands.folder));
+						ui.label("default application:");
+						ui.add(TextEdit::singleline(&mut self.commands.default_app));
+						ui.label("terminal:");
+						ui.add(TextEdit::singleline(&mut self.commands.terminal));
+					});
+
+					CollapsingHeader::new("Scan options").show(ui, |ui| {
+						ui.checkbox(&mut self.options.one_filesystem, "stay on one filesystem");
+						ui.checkbox(&mut self.options.follow_symlinks, "follow symlinks");
+						ui.checkbox(&mut self.options.archives, "look inside archives");
+						ui.horizontal(|ui| {
+							ui.label("size:");
+							ui.radio_value(&mut self.options.size_mode, SizeMode::Apparent, "apparent");
+							ui.radio_value(&mut self.options.size_mode, SizeMode::Disk, "on disk");
+						});
+						ui.label("du listings:");
+						ui.horizontal(|ui| {
+							ui.radio_value(&mut self.options.du.units, Units::Bytes, "du -ab");
+							ui.radio_value(&mut self.options.du.units, Units::Kilobytes, "du -ak");
+						});
+						ui.horizontal(|ui| {
+							ui.label("directories:");
+							ui.radio_value(&mut self.options.du.dir_sizes, DirSizes::Children, "sum of entries");
+							ui.radio_value(&mut self.options.du.dir_sizes, DirSizes::Listed, "listed totals");
+						});
+					});
+
+					if !self.options.excludes.is_empty() {
+						ui.label("Excluded:");
+						let mut included = None;
+						for (i, path) in self.options.excludes.iter().enumerate() {
+							ui.horizontal(|ui| {
+								if ui.small_button("x").clicked() {
+									included = Some(i);
+								}
+								ui.label(path.to_string_lossy());
+							});
+						}
+						if let Some(i) = included {
+							self.options.excludes.remove(i);
+						}
 					}
 
 					ui.label("Denied:");
@@ -156,9 +777,317 @@ impl State {
 							}
 						});
 
+					basket_changed = self.draw_basket_panel(ui);
+
+					self.draw_duplicates_panel(ui);
+
 				});
+
+			self.pointer_over_ui = ctx.is_pointer_over_area();
+			self.keyboard_over_ui = ctx.wants_keyboard_input();
 		});
 
+		if open_error.is_some() {
+			self.error = open_error;
+		}
+		if menu_closed {
+			self.menu = None;
+		}
+		if move_clicked {
+			self.start_move();
+		}
+		if move_cancelled {
+			self.move_picker = None;
+		}
+		if compress_clicked {
+			self.start_compression();
+		}
+		if compress_cancelled {
+			self.compress_prompt = None;
+		}
+		if let Some(i) = dismissed_compression {
+			self.compressions.remove(i);
+		}
+		for (action, path) in actions {
+			self.apply(action, path);
+		}
+		if undo_clicked {
+			self.undo_trash();
+		}
+		if basket_changed {
+			self.sync_basket_marks();
+		}
+
 		egui_macroquad::draw();
 	}
-}
\ No newline at end of file
+
+	// buttons shared by the details panel and the context menu, returns true if any of them was clicked
+	fn draw_node_actions(&self, ui: &mut Ui, path: &str, menu: bool, actions: &mut Vec<(NodeAction, String)>, open_error: &mut Option<String>) -> bool {
+		let Some(node) = self.tree.root().and_then(|root| root.find(path)) else {
+			return false;
+		};
+		let is_root = self.tree.root().is_some_and(|root| std::ptr::eq(root, node));
+		// entries of loaded scans, remote trees and archives may not exist here, only the basket and the ui work on them
+		// the menu always belongs to the selection, whose details checked the disk once
+		let on_disk = self.details.as_ref().is_some_and(|d| d.path() == path && d.on_disk());
+		let mut clicked = false;
+
+		let mut action = |ui: &mut Ui, label: &str, action: NodeAction| {
+			if ui.button(label).clicked() {
+				actions.push((action, path.to_string()));
+				clicked = true;
+			}
+		};
+
+		if menu {
+			if node.is_expanded() {
+				action(ui, "Collapse", NodeAction::Collapse);
+			}
+			else {
+				if node.is_dir() && !node.children().is_empty() {
+					action(ui, "Expand", NodeAction::Expand);
+				}
+				if !is_root {
+					action(ui, "Collapse parent", NodeAction::Collapse);
+				}
+			}
+			action(ui, "Zoom to", NodeAction::ZoomTo);
+			if on_disk && !node.is_dir() && archive::Kind::from_path(Path::new(path)).is_some() {
+				action(ui, "Look inside archive", NodeAction::OpenArchive);
+			}
+			if on_disk {
+				action(ui, "Rescan", NodeAction::Rescan);
+			}
+		}
+
+		// the basket only writes a script, which can be run on the machine the tree came from
+		if !is_root {
+			if on_disk {
+				action(ui, "Move to trash", NodeAction::Trash);
+			}
+			let label = if self.basket.contains(path) { "Remove from basket" } else { "Add to basket" };
+			action(ui, label, NodeAction::ToggleBasket);
+			if menu {
+				if on_disk {
+					action(ui, "Move to...", NodeAction::MoveTo);
+					if node.is_dir() {
+						action(ui, "Compress to .tar.zst", NodeAction::Compress);
+					}
+				}
+				action(ui, "Exclude", NodeAction::Exclude);
+			}
+		}
+
+		let commands = [
+			("Open containing folder", &self.commands.folder),
+			("Open with default application", &self.commands.default_app),
+			("Open terminal here", &self.commands.terminal),
+		];
+		for (label, template) in commands.into_iter().filter(|_| on_disk) {
+			if ui.button(label).clicked() {
+				*open_error = open::run(template, path).err().map(|e| format!("{template}: {e}"));
+				clicked = true;
+			}
+		}
+
+		if ui.button("Copy path").clicked() {
+			ui.ctx().output_mut(|o| o.copied_text = path.to_string());
+			clicked = true;
+		}
+
+		clicked
+	}
+
+	fn draw_export_panel(&mut self, ui: &mut Ui) {
+		ui.add(TextEdit::singleline(&mut self.export_path_buffer));
+
+		ComboBox::from_label("format")
+			.selected_text(self.export_format.name())
+			.show_ui(ui, |ui| {
+				for format in Format::ALL {
+					ui.selectable_value(&mut self.export_format, format, format.name());
+				}
+			});
+
+		ui.horizontal(|ui| {
+			let mut limited = self.export_options.filter.max_depth.is_some();
+			ui.checkbox(&mut limited, "max depth");
+			let mut depth = self.export_options.filter.max_depth.unwrap_or(3);
+			ui.add_enabled(limited, DragValue::new(&mut depth).clamp_range(0..=64));
+			self.export_options.filter.max_depth = limited.then_some(depth);
+		});
+
+		ui.horizontal(|ui| {
+			ui.label("min size:");
+			ui.add(TextEdit::singleline(&mut self.export_min_size_buffer).desired_width(60.0));
+		});
+
+		if matches!(self.export_format, Format::Csv | Format::Tsv) {
+			ui.horizontal_wrapped(|ui| {
+				for column in Column::ALL {
+					let mut enabled = self.export_options.columns.contains(&column);
+					if ui.checkbox(&mut enabled, column.name()).changed() {
+						// keep the columns in their usual order
+						self.export_options.columns = Column::ALL.into_iter()
+							.filter(|&c| if c == column { enabled } else { self.export_options.columns.contains(&c) })
+							.collect();
+					}
+				}
+			});
+			ui.horizontal(|ui| {
+				ui.label("rows:");
+				for rows in Rows::ALL {
+					ui.radio_value(&mut self.export_options.rows, rows, rows.name());
+				}
+			});
+		}
+
+		let min_bytes = text_to_bytes(&self.export_min_size_buffer);
+		if min_bytes.is_none() {
+			ui.colored_label(Color32::RED, "sizes look like 100K or 2G");
+		}
+
+		if ui.add_enabled(self.tree.root().is_some() && min_bytes.is_some(), Button::new("Export")).clicked() {
+			self.export_options.filter.min_bytes = min_bytes.unwrap_or(0);
+			if let Some(root) = self.tree.root() {
+				let path = Path::new(&self.export_path_buffer);
+				self.error = write_export(root, self.export_format, &self.export_options, path)
+					.err()
+					.map(|e| format!("{}: {e}", self.export_path_buffer));
+			}
+		}
+	}
+
+	// returns true if the basket contents changed
+	fn draw_basket_panel(&mut self, ui: &mut Ui) -> bool {
+		let mut changed = false;
+
+		ui.label(format!("Basket: {} items, {} reclaimable", self.basket.items().len(), bytes_to_text(self.basket.reclaimable())));
+
+		ui.horizontal(|ui| {
+			ui.radio_value(&mut self.basket.action, Action::Trash, "trash");
+			ui.radio_value(&mut self.basket.action, Action::Delete, "delete");
+		});
+
+		let idle = !self.basket.is_running();
+		ui.horizontal(|ui| {
+			if ui.add_enabled(idle, Button::new("Dry run")).clicked() {
+				self.dry_run = Some(self.basket.dry_run());
+			}
+			if ui.add_enabled(idle, Button::new("Execute")).clicked() {
+				self.basket.execute();
+				self.dry_run = None;
+				changed = true;
+			}
+			if ui.add_enabled(idle, Button::new("Clear")).clicked() {
+				self.basket.clear();
+				self.dry_run = None;
+				changed = true;
+			}
+		});
+
+		ui.horizontal(|ui| {
+			ui.add(TextEdit::singleline(&mut self.script_path_buffer).desired_width(120.0));
+			if ui.add_enabled(!self.basket.items().is_empty(), Button::new("Export script")).clicked() {
+				let path = Path::new(&self.script_path_buffer);
+				self.error = script::write_cleanup_script(path, self.basket.items(), self.basket.action, &self.options)
+					.err()
+					.map(|e| format!("{}: {e}", self.script_path_buffer));
+			}
+		});
+
+		if let Some((done, total)) = self.basket.progress() {
+			ui.label(format!("removed {done} of {total}"));
+		}
+
+		if let Some(lines) = &self.dry_run {
+			ui.label("Dry run:");
+			ScrollArea::vertical()
+				.id_source("dry run")
+				.max_height(150.0)
+				.auto_shrink([false; 2])
+				.show(ui, |ui| {
+					for line in lines {
+						ui.label(line);
+					}
+				});
+		}
+
+		if !self.basket.succeeded.is_empty() || !self.basket.failed.is_empty() {
+			ui.label("Removed:");
+			ScrollArea::vertical()
+				.id_source("removed")
+				.max_height(150.0)
+				.auto_shrink([false; 2])
+				.stick_to_bottom(true)
+				.show(ui, |ui| {
+					for path in self.basket.succeeded.iter() {
+						ui.label(path.to_string_lossy());
+					}
+				});
+
+			ui.label("Failed:");
+			ScrollArea::vertical()
+				.id_source("failed")
+				.max_height(150.0)
+				.auto_shrink([false; 2])
+				.stick_to_bottom(true)
+				.show(ui, |ui| {
+					for (path, error) in self.basket.failed.iter() {
+						ui.label(format!("{}: {error}", path.to_string_lossy()));
+					}
+				});
+		}
+
+		changed
+	}
+
+	fn draw_duplicates_panel(&mut self, ui: &mut Ui) {
+		ui.label("Duplicates:");
+
+		// the files are read to compare them, which only works for a tree scanned from the disk
+		let tree_ready = matches!(self.tree, Tree::Ready(_, _, _)) && self.local;
+		if ui.add_enabled(tree_ready && !self.duplicates.is_processing(), Button::new("Find duplicates")).clicked() {
+			if let Tree::Ready(root, _, _) = &self.tree {
+				let mut files = Vec::new();
+				root.collect_files(&mut files);
+				self.duplicates.scan(files.into_iter().map(|(path, _)| path).collect());
+			}
+		}
+		if !self.local {
+			ui.label("only for trees scanned from the disk");
+		}
+
+		match &self.duplicates {
+			Duplicates::Empty => {},
+			Duplicates::Processing(bytes, _) => {
+				ui.label(format!("hashed {}", bytes_to_text(*bytes)));
+			},
+			Duplicates::Ready(groups, wasted) => {
+				ui.label(format!("{} groups, {} wasted", groups.len(), bytes_to_text(*wasted)));
+				ScrollArea::vertical()
+					.id_source("duplicates")
+					.max_height(300.0)
+					.auto_shrink([false; 2])
+					.show(ui, |ui| {
+						for (i, group) in groups.iter().enumerate() {
+							let title = format!("{} x {} ({} wasted)", group.paths.len(), bytes_to_text(group.bytes), bytes_to_text(group.wasted()));
+							CollapsingHeader::new(title)
+								.id_source(("duplicate group", i))
+								.show(ui, |ui| {
+									for path in &group.paths {
+										ui.label(path.to_string_lossy());
+									}
+								});
+						}
+					});
+			},
+		}
+	}
+}
+
+fn write_export(root: &Node, format: Format, options: &export::Options, path: &Path) -> io::Result<()> {
+	let mut writer = BufWriter::new(File::create(path)?);
+	export::write(root, format, options, &mut writer)?;
+	writer.flush()
+}
@@ -0,0 +1,171 @@
+// moving files to the trash according to the freedesktop.org Trash specification
+// https://specifications.freedesktop.org/trash-spec/trashspec-latest.html
+
+use std::{fs::{self, OpenOptions}, io::{self, Write}, path::{Path, PathBuf}, env, time::{SystemTime, UNIX_EPOCH}};
+
+use fsexpl_core::text::time_to_text;
+
+// everything needed to put a trashed file back
+#[derive(Debug)]
+pub struct TrashedItem {
+	pub original: PathBuf,
+	pub trashed: PathBuf, // $trash/files/<name>
+	pub info: PathBuf, // $trash/info/<name>.trashinfo
+}
+
+pub fn move_to_trash(path: &Path) -> io::Result<TrashedItem> {
+	let file_name = path.file_name()
+		.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "cannot trash a filesystem root"))?
+		.to_string_lossy()
+		.to_string();
+
+	// only the parent is canonicalized, a symlink itself is trashed rather than its target
+	let parent = match path.parent() {
+		Some(p) if !p.as_os_str().is_empty() => p,
+		_ => Path::new("."),
+	};
+	let path = parent.canonicalize()?.join(&file_name);
+	let (trash, relative_to) = trash_dir_for(&path)?;
+
+	fs::create_dir_all(trash.join("files"))?;
+	fs::create_dir_all(trash.join("info"))?;
+
+	// paths in a $topdir trash are stored relative to $topdir
+	let stored_path = match &relative_to {
+		Some(top) => path.strip_prefix(top).unwrap_or(&path).to_path_buf(),
+		None => path.clone(),
+	};
+
+	let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0);
+	let contents = format!(
+		"[Trash Info]\nPath={}\nDeletionDate={}\n",
+		percent_encode(&stored_path),
+		time_to_text(seconds).replace(' ', "T"), // the spec asks for local time, UTC is the best we have without a timezone database
+	);
+
+	// creating the info file atomically reserves the name
+	for i in 1.. {
+		let name = if i == 1 { file_name.clone() } else { format!("{file_name}.{i}") };
+		let info = trash.join("info").join(format!("{name}.trashinfo"));
+		let trashed = trash.join("files").join(&name);
+
+		let mut info_file = match OpenOptions::new().write(true).create_new(true).open(&info) {
+			Ok(f) => f,
+			Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+			Err(e) => return Err(e),
+		};
+		if trashed.symlink_metadata().is_ok() {
+			drop(info_file);
+			fs::remove_file(&info)?;
+			continue;
+		}
+
+		let result = info_file.write_all(contents.as_bytes()).and_then(|_| fs::rename(&path, &trashed));
+		if let Err(e) = result {
+			drop(info_file);
+			let _ = fs::remove_file(&info);
+			return Err(e);
+		}
+
+		return Ok(TrashedItem { original: path, trashed, info });
+	}
+
+	unreachable!()
+}
+
+pub fn restore(item: &TrashedItem) -> io::Result<()> {
+	if item.original.symlink_metadata().is_ok() {
+		return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", item.original.to_string_lossy())));
+	}
+
+	fs::rename(&item.trashed, &item.original)?;
+	fs::remove_file(&item.info)
+}
+
+// (trash directory, $topdir if it is not the home trash)
+fn trash_dir_for(path: &Path) -> io::Result<(PathBuf, Option<PathBuf>)> {
+	let home_trash = match env::var_os("XDG_DATA_HOME") {
+		Some(data) if !data.is_empty() => PathBuf::from(data).join("Trash"),
+		_ => {
+			let home = env::var_os("HOME").ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "HOME is not set"))?;
+			PathBuf::from(home).join(".local/share/Trash")
+		}
+	};
+
+	// renaming only works within one filesystem, so files on other volumes use that volume's trash
+	let home_trash_device = home_trash.ancestors().find_map(|p| device(p).ok());
+	let device = device(path.parent().unwrap_or(path))?;
+	if home_trash_device == Some(device) {
+		return Ok((home_trash, None));
+	}
+
+	let top = top_dir(path, device);
+	let uid = uid();
+
+	// $topdir/.Trash/$uid is only valid if .Trash is a real, sticky directory
+	let shared = top.join(".Trash");
+	if let Ok(metadata) = shared.symlink_metadata() {
+		if metadata.is_dir() && is_sticky(&metadata) {
+			return Ok((shared.join(uid.to_string()), Some(top)));
+		}
+	}
+
+	Ok((top.join(format!(".Trash-{uid}")), Some(top)))
+}
+
+// the topmost ancestor still on the same device
+fn top_dir(path: &Path, device_id: u64) -> PathBuf {
+	let mut top = path;
+	while let Some(parent) = top.parent() {
+		if device(parent).ok() != Some(device_id) {
+			break;
+		}
+		top = parent;
+	}
+	top.to_path_buf()
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> io::Result<u64> {
+	use std::os::unix::fs::MetadataExt;
+	Ok(path.metadata()?.dev())
+}
+
+#[cfg(not(unix))]
+fn device(path: &Path) -> io::Result<u64> {
+	path.metadata().map(|_| 0)
+}
+
+#[cfg(unix)]
+fn uid() -> u32 {
+	use std::os::unix::fs::MetadataExt;
+	// the owner of our own process directory, avoids linking libc for getuid
+	fs::metadata("/proc/self").map(|m| m.uid()).unwrap_or(0)
+}
+
+#[cfg(not(unix))]
+fn uid() -> u32 {
+	0
+}
+
+#[cfg(unix)]
+fn is_sticky(metadata: &fs::Metadata) -> bool {
+	use std::os::unix::fs::PermissionsExt;
+	metadata.permissions().mode() & 0o1000 != 0
+}
+
+#[cfg(not(unix))]
+fn is_sticky(_metadata: &fs::Metadata) -> bool {
+	false
+}
+
+pub fn percent_encode(path: &Path) -> String {
+	let mut encoded = String::new();
+	for byte in path.to_string_lossy().bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => encoded.push(byte as char),
+			_ => encoded.push_str(&format!("%{:02X}", byte)),
+		}
+	}
+	encoded
+}
\ No newline at end of file
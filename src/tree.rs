@@ -1,40 +1,22 @@
-use std::{thread, sync::mpsc::{channel, Receiver}, path::{Path, PathBuf}};
+use std::{sync::mpsc::Receiver, path::{Path, PathBuf}};
 
-use egui_macroquad::macroquad::prelude::Rect;
+use fsexpl_core::{scan::{self, Message, ScanOptions}, node::Weight};
 
 use crate::node::Node;
 
-#[derive(Debug)]
-pub enum Message {
-	Denied(PathBuf),
-	Progress(u64), // total bytes
-	Finished(Option<Node>), // Some if successful, None if invalid
-}
-
 pub enum Tree {
 	Empty,
 	Processing(u64, u64, Receiver<Message>), // bytes, files cummulative, message receiver
-	Ready(Node, u64, u64), // tree, bytes, files
+	Ready(Box<Node>, u64, u64), // tree, bytes, files
 }
 
 impl Tree {
-	pub fn scan(&mut self, path: &Path) {
+	pub fn scan(&mut self, path: &Path, options: &ScanOptions) {
 		if self.is_processing() {
 			return;
 		}
 
-		let (mut sender, receiver) = channel::<Message>();
-		
-		let path_buf = path.to_owned();
-		thread::spawn(move || {
-			let mut bytes = 0;
-			let mut counter = 0;
-			let node = Node::new(&path_buf, Rect::new(0.0, 0.0, 1.0, 1.0), &mut sender, &mut bytes, &mut counter);
-
-			sender.send(Message::Finished(node)).unwrap();
-		});
-
-		*self = Self::Processing(0, 0, receiver);
+		*self = Self::Processing(0, 0, scan::spawn(path, options));
 	}
 
 	// returns true if the tree finished loading
@@ -44,16 +26,18 @@ impl Tree {
 		if let Tree::Processing(bytes, files, receiver) = self {
 			while let Ok(m) = receiver.try_recv() {
 				match m {
-					Message::Progress(new_bytes) => { *bytes = new_bytes; *files += 100;},
+					Message::Progress(new_bytes, new_files) => { *bytes = new_bytes; *files = new_files; },
 					Message::Denied(path) => denied.push(path),
-					Message::Finished(node) => finished_node = Some((node, *bytes, *files)),
+					Message::Finished(node) => finished_node = Some(node),
 				}
 			}
 		}
 
 		if let Some(node) = finished_node {
-			if let (Some(node), bytes, files) = node {
-				*self = Tree::Ready(node, bytes, files);
+			if let Some(node) = node {
+				let node = Node::from(node);
+				let (bytes, files) = (node.bytes(), node.files());
+				*self = Tree::Ready(Box::new(node), bytes, files);
 				return true;
 			}
 			else {
@@ -64,27 +48,81 @@ impl Tree {
 		false
 	}
 
-	pub fn _is_empty(&self) -> bool {
+	// replaces the subtree at the node's path with a freshly scanned one, gives it back if its parent is no longer in the tree
+	pub fn replace(&mut self, node: Node, weight: Weight) -> Result<(), Box<Node>> {
+		let mut inserted = Ok(());
+		if let Tree::Ready(root, _, _) = self {
+			let path = node.get_full_path();
+			if path == root.get_full_path() {
+				**root = node;
+			}
+			else {
+				root.remove(&path, weight);
+				inserted = root.insert(node, weight);
+			}
+		}
+		self.update_totals();
+		inserted
+	}
+
+	pub fn root(&self) -> Option<&Node> {
 		match self {
-			&Self::Empty => true,
-			&Self::Processing(_, _, _) => false,
-			&Self::Ready(_, _, _) => false,
+			Tree::Ready(root, _, _) => Some(root),
+			_ => None,
+		}
+	}
+
+	// recomputes the scan-wide totals after the tree was modified
+	pub fn update_totals(&mut self) {
+		if let Tree::Ready(root, bytes, files) = self {
+			*bytes = root.bytes();
+			*files = root.files();
+		}
+	}
+
+	pub fn _is_empty(&self) -> bool {
+		match *self {
+			Self::Empty => true,
+			Self::Processing(_, _, _) => false,
+			Self::Ready(_, _, _) => false,
 		}
 	}
 
 	pub fn is_processing(&self) -> bool {
-		match self {
-			&Self::Empty => false,
-			&Self::Processing(_, _, _) => true,
-			&Self::Ready(_, _, _) => false,
+		match *self {
+			Self::Empty => false,
+			Self::Processing(_, _, _) => true,
+			Self::Ready(_, _, _) => false,
 		}
 	}
 
 	pub fn _is_ready(&self) -> bool {
-		match self {
-			&Self::Empty => false,
-			&Self::Processing(_, _, _) => false,
-			&Self::Ready(_, _, _) => true,
+		match *self {
+			Self::Empty => false,
+			Self::Processing(_, _, _) => false,
+			Self::Ready(_, _, _) => true,
 		}
 	}
-}
\ No newline at end of file
+}
+
+// scans a single subtree of a ready tree again in the background
+pub struct Rescan(Receiver<Message>);
+
+impl Rescan {
+	pub fn start(path: &Path, options: &ScanOptions) -> Self {
+		Self(scan::spawn(path, options))
+	}
+
+	// Some once the scan finished, with None inside if the path couldn't be scanned
+	pub fn process(&mut self, denied: &mut Vec<PathBuf>) -> Option<Option<Node>> {
+		while let Ok(m) = self.0.try_recv() {
+			match m {
+				Message::Progress(_, _) => {},
+				Message::Denied(path) => denied.push(path),
+				Message::Finished(node) => return Some(node.map(Node::from)),
+			}
+		}
+
+		None
+	}
+}